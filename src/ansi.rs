@@ -0,0 +1,265 @@
+use unicode_width::UnicodeWidthChar;
+
+/// A terminal color: one of the 256 indexed colors or a truecolor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Color {
+    Index(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// The SGR attributes of a character.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub inverse: bool,
+}
+
+/// A character as shown on screen, with its style.
+pub type Cell = (char, Style);
+
+/// Columns between tab stops.
+pub const TAB: usize = 8;
+
+impl Style {
+    /// This style, with what it leaves unset taken from `base`.
+    pub fn over(self, base: Style) -> Style {
+        Style {
+            fg: self.fg.or(base.fg),
+            bg: self.bg.or(base.bg),
+            bold: self.bold || base.bold,
+            dim: self.dim || base.dim,
+            italic: self.italic || base.italic,
+            underline: self.underline || base.underline,
+            inverse: self.inverse || base.inverse,
+        }
+    }
+
+    /// The escape that resets the terminal to this style.
+    pub fn sgr(&self) -> String {
+        let mut codes = vec!["0".to_string()];
+        for (on, code) in [
+            (self.bold, "1"),
+            (self.dim, "2"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+            (self.inverse, "7"),
+        ] {
+            if on {
+                codes.push(code.to_string());
+            }
+        }
+        if let Some(fg) = self.fg {
+            codes.push(color_code(fg, 30, 90, 38));
+        }
+        if let Some(bg) = self.bg {
+            codes.push(color_code(bg, 40, 100, 48));
+        }
+        format!("\x1b[{}m", codes.join(";"))
+    }
+
+    /// Apply the parameters of an SGR escape, `ESC [ ... m`.
+    fn apply(&mut self, params: &str) {
+        let mut codes = params
+            .split([';', ':'])
+            .map(|p| p.parse::<u16>().unwrap_or(0));
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                22 => {
+                    self.bold = false;
+                    self.dim = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                30..=37 => self.fg = Some(Color::Index(code as u8 - 30)),
+                38 => self.fg = extended(&mut codes),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some(Color::Index(code as u8 - 40)),
+                48 => self.bg = extended(&mut codes),
+                49 => self.bg = None,
+                90..=97 => self.fg = Some(Color::Index(code as u8 - 90 + 8)),
+                100..=107 => self.bg = Some(Color::Index(code as u8 - 100 + 8)),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn color_code(color: Color, base: u8, bright: u8, extended: u8) -> String {
+    match color {
+        Color::Index(n) if n < 8 => (base + n).to_string(),
+        Color::Index(n) if n < 16 => (bright + n - 8).to_string(),
+        Color::Index(n) => format!("{};5;{}", extended, n),
+        Color::Rgb(r, g, b) => format!("{};2;{};{};{}", extended, r, g, b),
+    }
+}
+
+/// The color after a 38 or 48: `5;n` or `2;r;g;b`.
+fn extended(codes: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    let mut next = || codes.next().map(|c| c.min(255) as u8);
+    match next()? {
+        5 => Some(Color::Index(next()?)),
+        2 => Some(Color::Rgb(next()?, next()?, next()?)),
+        _ => None,
+    }
+}
+
+/// The characters of a line of child output as they would show on a
+/// terminal. SGR escapes become styles; carriage returns, backspaces and
+/// tabs move within the line; any other escape or control character is
+/// dropped, so nothing can move the cursor around the screen viri draws.
+pub fn parse(text: &str) -> Vec<Cell> {
+    let mut cells: Vec<Cell> = Vec::new();
+    let mut col: usize = 0;
+    let mut style = Style::default();
+    let mut chars = text.chars().peekable();
+
+    let put = |cells: &mut Vec<Cell>, col: &mut usize, cell: Cell| {
+        match cells.get_mut(*col) {
+            Some(old) => *old = cell,
+            None => cells.push(cell),
+        }
+        *col += 1;
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters, intermediates, then a final byte
+                Some('[') => {
+                    let mut params = String::new();
+                    let mut last = None;
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            last = Some(c);
+                            break;
+                        }
+                        params.push(c);
+                    }
+                    let private = params.starts_with(['?', '<', '=', '>']);
+                    if last == Some('m') && !private {
+                        style.apply(&params);
+                    }
+                }
+                // strings ended by BEL or ST, such as window titles
+                Some(']') | Some('P') | Some('X') | Some('^') | Some('_') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                // charset designations and the like take one more byte
+                Some(c) if (' '..='/').contains(&c) => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            '\r' => col = 0,
+            '\x08' => col = col.saturating_sub(1),
+            '\t' => {
+                let shown: usize =
+                    cells[..col].iter().map(|&(c, _)| cols(c)).sum();
+                for _ in 0..TAB - shown % TAB {
+                    put(&mut cells, &mut col, (' ', style));
+                }
+            }
+            c if c.is_control() => {}
+            c => put(&mut cells, &mut col, (c, style)),
+        }
+    }
+    cells
+}
+
+/// `text` without its escapes.
+pub fn strip(text: &str) -> String {
+    parse(text).into_iter().map(|(c, _)| c).collect()
+}
+
+/// Columns `c` takes on screen: two for wide characters such as CJK and
+/// most emoji, none for combining marks and controls.
+pub fn cols(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// Write `cells` back out with the escapes for their styles, ending with
+/// the default style.
+pub fn render(cells: &[Cell]) -> String {
+    let mut out = String::new();
+    let mut current = Style::default();
+    for &(c, style) in cells {
+        if style != current {
+            out.push_str(&style.sgr());
+            current = style;
+        }
+        out.push(c);
+    }
+    if current != Style::default() {
+        out.push_str("\x1b[0m");
+    }
+    out
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn width(text: &str) -> usize {
+        parse(text).iter().map(|&(c, _)| cols(c)).sum()
+    }
+
+    #[test]
+    pub fn parse_sgr() {
+        let cells = parse("\x1b[1;31mERROR\x1b[0m: \x1b[38;5;208mx\x1b[m");
+        assert_eq!(strip("\x1b[1;31mERROR\x1b[0m: x"), "ERROR: x");
+        let red = Style {
+            fg: Some(Color::Index(1)),
+            bold: true,
+            ..Style::default()
+        };
+        assert_eq!(cells[0], ('E', red));
+        assert_eq!(cells[5], (':', Style::default()));
+        assert_eq!(cells[7].1.fg, Some(Color::Index(208)));
+        assert_eq!(
+            parse("\x1b[48;2;1;2;3mx")[0].1.bg,
+            Some(Color::Rgb(1, 2, 3))
+        );
+    }
+
+    #[test]
+    pub fn parse_drops_cursor_controls() {
+        assert_eq!(strip("a\x1b[2J\x1b[10;1Hb\x1b[?25l"), "ab");
+        assert_eq!(strip("\x1b]0;title\x07prompt\x1b(B> "), "prompt> ");
+        assert_eq!(strip("50%\r100%"), "100%");
+        assert_eq!(strip("ab\x08c\x07"), "ac");
+        assert_eq!(width("a\tb"), 9);
+        assert_eq!(width("日本\tb"), 9);
+        assert_eq!(width("e\u{301}"), 1);
+    }
+
+    #[test]
+    pub fn render_round_trip() {
+        let text = "\x1b[0;1;32mok\x1b[0m done";
+        assert_eq!(render(&parse(text)), text);
+        assert_eq!(render(&parse("\x1b[34mblue")), "\x1b[0;34mblue\x1b[0m");
+    }
+}
@@ -1,16 +1,22 @@
-use super::{output, cursor::Cursor};
+use super::cursor::Cursor;
 use anyhow::Result;
-use std::{process::Stdio, sync::mpsc::Sender};
-use tokio::{io::{
-        AsyncBufReadExt,
-        AsyncWriteExt,
-        BufReader,
-        BufWriter,
-    }, join, process::{
-        Command,
-        ChildStdout,
-        ChildStdin
-    }, runtime::Runtime, sync::mpsc::{self, UnboundedReceiver}};
+use std::{
+    process::{ExitStatus, Stdio},
+    sync::mpsc::Sender,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader, BufWriter},
+    join,
+    process::{ChildStdin, Command},
+    runtime::Runtime,
+    sync::mpsc::UnboundedReceiver,
+    time::timeout,
+};
+
+/// How long an unterminated line may wait before it is shown anyway: REPLs
+/// leave their prompt waiting for input without a newline.
+const PARTIAL_LINE: Duration = Duration::from_millis(50);
 
 #[derive(Debug)]
 pub enum Message {
@@ -19,39 +25,72 @@ pub enum Message {
     Kill,
 }
 
+/// What the child thread tells the main loop.
+#[derive(Debug)]
+pub enum Update {
+    Started(u32),
+    Output(String),
+    /// a line written to stderr
+    Error(String),
+    Exited(ExitStatus),
+}
+
+/// What is known about the wrapped program.
+#[derive(Clone, Debug, Default)]
+pub struct Process {
+    pub pid: Option<u32>,
+    /// how it ended, once it has
+    pub exit: Option<ExitStatus>,
+}
+
 pub fn run_child(
     proc: String,
     args: &[String],
     command_recv: UnboundedReceiver<Message>,
-    output_send: Sender<String>,
+    output_send: Sender<Update>,
     rt: &Runtime,
 ) -> Result<()> {
     let mut cmd = Command::new(&proc);
     cmd.args(args);
     cmd.stdin(Stdio::piped());
     cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
 
     // let ( send, recv ) = mpsc::channel(25);
     // let console = output::Output::new(recv);
 
     rt.block_on(async {
         let mut child = cmd.spawn()?;
+        if let Some(pid) = child.id() {
+            output_send.send(Update::Started(pid))?;
+        }
         let cin = BufWriter::new(
-            child.stdin.take().expect("failed to get child input")
+            child.stdin.take().expect("failed to get child input"),
         );
         let cout = BufReader::new(
-            child.stdout.take().expect("failed to get child output")
+            child.stdout.take().expect("failed to get child output"),
+        );
+        let cerr = BufReader::new(
+            child.stderr.take().expect("failed to get child errors"),
         );
 
         let cin = rt.spawn(child_input(cin, command_recv));
-        let cout = rt.spawn(child_output(cout, output_send));
+        let cout =
+            rt.spawn(child_output(cout, output_send.clone(), Update::Output));
+        let cerr =
+            rt.spawn(child_output(cerr, output_send.clone(), Update::Error));
         // let console = rt.spawn(console.run_output());
 
-        // let cin = join!(cin);
-        let (cin, cout) = join!(cin, cout);
+        // its output ends when the child exits
+        let exited = async {
+            cout.await??;
+            cerr.await??;
+            output_send.send(Update::Exited(child.wait().await?))?;
+            Ok::<(), anyhow::Error>(())
+        };
+        let (cin, exited) = join!(cin, exited);
         cin??;
-        cout??;
-        child.kill().await?;
+        exited?;
         Ok(())
     })
 }
@@ -63,8 +102,7 @@ async fn child_input(
     loop {
         let message = command_recv.recv().await.expect("no message");
         match message {
-            Message::Input(_i) => {
-            }
+            Message::Input(_i) => {}
 
             Message::Exec(i) => {
                 cin.write_all(i.as_bytes()).await?;
@@ -77,15 +115,37 @@ async fn child_input(
     Ok(())
 }
 
-async fn child_output(
-    cout: BufReader<ChildStdout>,
-    console: Sender<String>
+/// Send each line read from `cout` as an update made by `update`.
+async fn child_output<R: AsyncRead + Unpin>(
+    mut cout: BufReader<R>,
+    console: Sender<Update>,
+    update: fn(String) -> Update,
 ) -> Result<()> {
-    let mut cout = cout.lines();
-    while let Some(line) = cout.next_line().await? {
-        console.send(line)?;
+    let mut line = Vec::new();
+    loop {
+        match timeout(PARTIAL_LINE, cout.read_until(b'\n', &mut line)).await {
+            Ok(read) => {
+                let eof = read? == 0;
+                if (eof || line.ends_with(b"\n")) && !line.is_empty() {
+                    console.send(update(take_line(&mut line)))?;
+                }
+                if eof {
+                    break;
+                }
+            }
+            Err(_) if !line.is_empty() => {
+                console.send(update(take_line(&mut line)))?
+            }
+            Err(_) => {}
+        }
     }
-    print!("no more entries...\n\r");
     Ok(())
 }
 
+fn take_line(line: &mut Vec<u8>) -> String {
+    let text = String::from_utf8_lossy(line)
+        .trim_end_matches(&['\n', '\r'][..])
+        .to_string();
+    line.clear();
+    text
+}
@@ -0,0 +1,495 @@
+use super::{
+    config::expand_path,
+    cursor::Cursor,
+    export::{self, Kind},
+    history::{Input, Source},
+    import,
+    replay::{self, Control},
+    state::{self, Mode, State},
+    substitute::Substitution,
+};
+use anyhow::{anyhow, bail, Result};
+use std::fs;
+
+/// Ex commands and the shortest abbreviation each accepts, checked in order.
+static COMMANDS: &[(&str, usize)] = &[
+    ("write", 1),
+    ("whistory", 2),
+    ("quit", 1),
+    ("set", 2),
+    ("history", 3),
+    ("map", 3),
+    ("nmap", 2),
+    ("imap", 2),
+    ("source", 2),
+    ("substitute", 1),
+    ("export", 1),
+    ("replay", 3),
+    ("highlight", 2),
+    ("match", 3),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MapMode {
+    Normal,
+    Insert,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Set(Vec<String>),
+    History(Option<usize>),
+    Import {
+        format: import::Format,
+        path: String,
+    },
+    Export {
+        format: export::Format,
+        path: String,
+    },
+    Write {
+        path: String,
+        inputs_only: bool,
+    },
+    Quit {
+        force: bool,
+    },
+    Map {
+        mode: MapMode,
+        binding: Option<(String, String)>,
+    },
+    Source(String),
+    Replay(Control),
+    /// `:highlight {group} {attributes}`
+    Highlight(Vec<String>),
+    /// `:match /{pattern}/ {attributes}`
+    Match(String),
+    /// `:s` on the input line, or `:%s` on the recalled history entry
+    Substitute {
+        history: bool,
+        spec: String,
+    },
+}
+
+pub fn parse(line: &str) -> Result<Command> {
+    let line = line.trim().trim_start_matches(':');
+    let (history, line) = match line.strip_prefix('%') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let end = line
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(line.len());
+    let (name, rest) = line.split_at(end);
+    let (force, rest) = match rest.strip_prefix('!') {
+        Some(rest) => (true, rest.trim()),
+        None => (false, rest.trim()),
+    };
+    let args: Vec<&str> = rest.split_whitespace().collect();
+
+    let full = COMMANDS
+        .iter()
+        .find(|(full, min)| name.len() >= *min && full.starts_with(name))
+        .map(|(full, _)| *full)
+        .ok_or_else(|| anyhow!("not a command: {}", line))?;
+    if history && full != "substitute" {
+        bail!("no range allowed: {}", full);
+    }
+
+    Ok(match full {
+        "set" => Command::Set(set_args(rest)),
+        "history" => match args[..] {
+            [] => Command::History(None),
+            ["import", format, path] => Command::Import {
+                format: format.parse()?,
+                path: path.to_string(),
+            },
+            ["import", ..] => {
+                bail!("usage: history import {{format}} {{file}}")
+            }
+            [n] => Command::History(Some(
+                n.parse().map_err(|_| anyhow!("invalid count: {}", n))?,
+            )),
+            _ => bail!("trailing characters: {}", rest),
+        },
+        "write" | "whistory" => match rest {
+            "" => bail!("no file name"),
+            path => Command::Write {
+                path: path.to_string(),
+                inputs_only: full == "whistory",
+            },
+        },
+        "quit" => Command::Quit { force },
+        "export" => match args[..] {
+            [format, path] => Command::Export {
+                format: format.parse()?,
+                path: path.to_string(),
+            },
+            _ => bail!("usage: export {{json|text|script}} {{file}}"),
+        },
+        "map" | "nmap" | "imap" => Command::Map {
+            mode: match full {
+                "imap" => MapMode::Insert,
+                _ => MapMode::Normal,
+            },
+            binding: match args[..] {
+                [] => None,
+                [lhs, rhs] => Some((lhs.to_string(), rhs.to_string())),
+                _ => bail!("usage: {} {{keys}} {{function}}", full),
+            },
+        },
+        "source" => match rest {
+            "" => bail!("no file name"),
+            path => Command::Source(path.to_string()),
+        },
+        "replay" => Command::Replay(match rest {
+            "" => bail!("usage: replay {{file}}|pause|resume|step|skip|abort"),
+            "pause" => Control::Pause,
+            "resume" => Control::Resume,
+            "step" => Control::Step,
+            "skip" => Control::Skip,
+            "abort" => Control::Abort,
+            path => Control::Start(path.to_string()),
+        }),
+        "highlight" => {
+            Command::Highlight(args.iter().map(|a| a.to_string()).collect())
+        }
+        "match" => Command::Match(rest.to_string()),
+        "substitute" => Command::Substitute {
+            history,
+            spec: line[end..].to_string(),
+        },
+        _ => unreachable!("command not handled: {}", full),
+    })
+}
+
+/// Run one ex command line, leaving any output or error in `State::status`.
+pub fn execute(line: &str, mut state: State) -> State {
+    match parse(line).and_then(|cmd| run(cmd, &mut state)) {
+        Ok(message) => state.status = message,
+        Err(e) => state.status = Some(format!("E: {}", e)),
+    }
+    state
+}
+
+/// Run every command in a file, as with `:source`.
+pub fn source(path: &str, mut state: State) -> State {
+    if let Err(e) = run(Command::Source(path.to_string()), &mut state) {
+        state.status = Some(format!("E: {}", e));
+    }
+    state
+}
+
+fn run(cmd: Command, state: &mut State) -> Result<Option<String>> {
+    match cmd {
+        Command::Set(args) if args.is_empty() => Ok(Some(state.options.show())),
+
+        Command::Set(args) => {
+            let mut shown = Vec::new();
+            for arg in args {
+                shown.extend(state.options.set(&arg)?);
+            }
+            Ok(Some(shown.join("\n")).filter(|s| !s.is_empty()))
+        }
+
+        Command::History(count) => {
+            let inputs: Vec<&Input> = state.history.input_entries().collect();
+            let skip =
+                inputs.len() - count.unwrap_or(inputs.len()).min(inputs.len());
+            Ok(Some(
+                inputs
+                    .iter()
+                    .enumerate()
+                    .skip(skip)
+                    .map(|(n, i)| {
+                        format!("{:>5}  {:>7}  {}", n + 1, i.meta.ago(), i.text)
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+            ))
+        }
+
+        Command::Import { format, path } => {
+            let imported = import::read(&expand_path(&path), format)
+                .map_err(|e| anyhow!("{}: {}", path, e))?;
+            let count = state.history.import(imported, &state.options)?;
+            Ok(Some(format!("imported {} inputs from {}", count, path)))
+        }
+
+        Command::Export { format, path } => {
+            let entries =
+                state.history.entries().map(|(source, meta)| match source {
+                    Source::ChildInput(c) => (Kind::Input, c.to_string(), meta),
+                    Source::ChildOutput(c) => {
+                        (Kind::Output, c.to_string(), meta)
+                    }
+                    Source::ChildError(c) => (Kind::Error, c.to_string(), meta),
+                });
+            fs::write(expand_path(&path), export::render(entries, format))?;
+            Ok(Some(format!("\"{}\" written", path)))
+        }
+
+        Command::Write { path, inputs_only } => {
+            let lines: Vec<String> = state
+                .history
+                .values()
+                .filter_map(|h| match h {
+                    Source::ChildInput(c) => Some(c.to_string()),
+                    Source::ChildOutput(_) | Source::ChildError(_)
+                        if inputs_only =>
+                    {
+                        None
+                    }
+                    Source::ChildOutput(c) | Source::ChildError(c) => {
+                        Some(c.to_string())
+                    }
+                })
+                .collect();
+            let mut text = lines.join("\n");
+            text.push('\n');
+            fs::write(expand_path(&path), text)?;
+            Ok(Some(format!("\"{}\" {}L written", path, lines.len())))
+        }
+
+        Command::Quit { force } => {
+            if !force && !state.input.is_empty() {
+                bail!("input line not empty (add ! to override)");
+            }
+            state.mode = Mode::Quit;
+            Ok(None)
+        }
+
+        Command::Map { mode, binding } => {
+            let keys = match mode {
+                MapMode::Normal => &mut state.keys,
+                MapMode::Insert => &mut state.insert_keys,
+            };
+            match binding {
+                None => Ok(Some(
+                    keys.bindings()
+                        .iter()
+                        .map(|(k, f)| format!("{:<8}{}", k, f))
+                        .collect::<Vec<String>>()
+                        .join("\n"),
+                )),
+                Some((lhs, rhs)) => {
+                    if !state::is_function(&rhs) {
+                        bail!("unknown function: {}", rhs);
+                    }
+                    keys.insert(&lhs, rhs);
+                    Ok(None)
+                }
+            }
+        }
+
+        Command::Replay(ctl) => replay::control(ctl, state),
+
+        Command::Highlight(args) => {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            state.highlights.set(&args)
+        }
+
+        Command::Match(spec) => state.highlights.add_rule(&spec),
+
+        Command::Substitute { history, spec } => {
+            let sub = Substitution::parse(&spec)?;
+            let target = if history {
+                state
+                    .history
+                    .recalled()
+                    .ok_or_else(|| anyhow!("no history"))?
+                    .to_string()
+            } else {
+                state.input.to_string()
+            };
+            let result = sub
+                .apply(&target)
+                .ok_or_else(|| anyhow!("pattern not found: {}", spec))?;
+            state.input = Cursor::from(result, String::new());
+            Ok(None)
+        }
+
+        Command::Source(path) => {
+            let text = fs::read_to_string(expand_path(&path))
+                .map_err(|e| anyhow!("{}: {}", path, e))?;
+            let mut errors = Vec::new();
+            // lines between `profile {program}...` and `endprofile` only
+            // apply when wrapping one of the named programs
+            let mut active = true;
+            for (i, line) in text.lines().enumerate() {
+                let line = line.trim();
+                if let Some(names) = line.strip_prefix("profile ") {
+                    active =
+                        names.split_whitespace().any(|n| n == state.program);
+                    continue;
+                }
+                if line == "endprofile" {
+                    active = true;
+                    continue;
+                }
+                if !active || line.is_empty() || line.starts_with('"') {
+                    continue;
+                }
+                if let Err(e) = parse(line).and_then(|cmd| run(cmd, state)) {
+                    errors.push(format!("{}:{}: {}", path, i + 1, e));
+                }
+            }
+            if !errors.is_empty() {
+                bail!(errors.join("\n"));
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// Split `:set` arguments on whitespace, except where escaped as `\ `.
+fn set_args(rest: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|n| n.is_whitespace()) => {
+                arg.extend(chars.next());
+            }
+            c if c.is_whitespace() => {
+                if !arg.is_empty() {
+                    args.push(std::mem::take(&mut arg));
+                }
+            }
+            c => arg.push(c),
+        }
+    }
+    if !arg.is_empty() {
+        args.push(arg);
+    }
+    args
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn parse_abbreviations() {
+        assert_eq!(parse("q").unwrap(), Command::Quit { force: false });
+        assert_eq!(parse(":quit!").unwrap(), Command::Quit { force: true });
+        assert_eq!(
+            parse("se timeoutlen=200").unwrap(),
+            Command::Set(vec!["timeoutlen=200".to_string()])
+        );
+        assert_eq!(parse("his 10").unwrap(), Command::History(Some(10)));
+        assert_eq!(
+            parse(r"set histignore=^drop\ table nohistexpand").unwrap(),
+            Command::Set(vec![
+                "histignore=^drop table".to_string(),
+                "nohistexpand".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    pub fn parse_import() {
+        assert_eq!(
+            parse("history import zsh ~/.zsh_history").unwrap(),
+            Command::Import {
+                format: import::Format::Zsh,
+                path: "~/.zsh_history".to_string()
+            }
+        );
+        assert!(parse("history import fish ~/.fish_history").is_err());
+        assert!(parse("history import zsh").is_err());
+    }
+
+    #[test]
+    pub fn parse_export() {
+        assert_eq!(
+            parse("exp json session.jsonl").unwrap(),
+            Command::Export {
+                format: export::Format::Json,
+                path: "session.jsonl".to_string()
+            }
+        );
+        assert!(parse("export csv out.csv").is_err());
+    }
+
+    #[test]
+    pub fn parse_write() {
+        assert_eq!(
+            parse("w out.txt").unwrap(),
+            Command::Write {
+                path: "out.txt".to_string(),
+                inputs_only: false
+            }
+        );
+        assert_eq!(
+            parse("wh out.txt").unwrap(),
+            Command::Write {
+                path: "out.txt".to_string(),
+                inputs_only: true
+            }
+        );
+        assert!(parse("w").is_err());
+    }
+
+    #[test]
+    pub fn parse_map() {
+        assert_eq!(
+            parse("imap jk Normal").unwrap(),
+            Command::Map {
+                mode: MapMode::Insert,
+                binding: Some(("jk".to_string(), "Normal".to_string()))
+            }
+        );
+        assert_eq!(
+            parse("map").unwrap(),
+            Command::Map {
+                mode: MapMode::Normal,
+                binding: None
+            }
+        );
+        assert!(parse("nmap x").is_err());
+    }
+
+    #[test]
+    pub fn parse_substitute() {
+        assert_eq!(
+            parse("s/a/b/g").unwrap(),
+            Command::Substitute {
+                history: false,
+                spec: "/a/b/g".to_string()
+            }
+        );
+        assert_eq!(
+            parse("%s!a!b!").unwrap(),
+            Command::Substitute {
+                history: true,
+                spec: "!a!b!".to_string()
+            }
+        );
+        assert!(parse("%set").is_err());
+    }
+
+    #[test]
+    pub fn parse_replay() {
+        assert_eq!(
+            parse("rep demo.sql").unwrap(),
+            Command::Replay(Control::Start("demo.sql".to_string()))
+        );
+        assert_eq!(
+            parse("replay skip").unwrap(),
+            Command::Replay(Control::Skip)
+        );
+        assert!(parse("replay").is_err());
+    }
+
+    #[test]
+    pub fn parse_unknown() {
+        assert!(parse("frobnicate").is_err());
+        assert!(parse("h").is_err());
+    }
+}
@@ -1,4 +1,394 @@
-use std::collections::HashMap;
+use super::{statusline::STATUSLINE, syntax};
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use std::{collections::HashMap, env, path::PathBuf, time::Duration};
+
+/// milliseconds to wait for more keys when a binding is a prefix of another
+pub const TIMEOUT_LEN: u64 = 1000;
+
+/// milliseconds to wait between lines when replaying a script
+pub const REPLAY_DELAY: u64 = 500;
+
+/// Names of every option, in the order `:set all` shows them.
+static OPTIONS: &[&str] = &[
+    "timeoutlen",
+    "histexpand",
+    "histfile",
+    "histcontrol",
+    "histignore",
+    "prompt",
+    "replaydelay",
+    "display",
+    "statusline",
+    "cursorshape",
+    "syntax",
+    "keywords",
+    "autopairs",
+    "multiline",
+    "continuation",
+    "editexec",
+];
+
+/// Runtime options changed with `:set`.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub timeoutlen: u64,
+    pub histexpand: bool,
+    pub histfile: Option<PathBuf>,
+    pub histcontrol: HistControl,
+    pub histignore: Vec<Regex>,
+    /// matches the child's prompt, which a replay waits for between lines
+    pub prompt: Option<Regex>,
+    pub replaydelay: u64,
+    pub display: Display,
+    /// format of the status line, empty to hide it; see `statusline::render`
+    pub statusline: String,
+    pub cursorshape: CursorShapes,
+    /// the lexer for the input line, empty for plain text
+    pub syntax: String,
+    /// highlighted as keywords besides those of `syntax`
+    pub keywords: Vec<String>,
+    /// close brackets and quotes as they are opened in insert mode
+    pub autopairs: bool,
+    /// Enter in insert mode starts a new line while the input is incomplete
+    pub multiline: bool,
+    /// an input matching this is incomplete too
+    pub continuation: Option<Regex>,
+    /// send the input as soon as `v` has edited it, instead of loading it
+    /// back into the input line
+    pub editexec: bool,
+}
+
+/// How viri uses the terminal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Display {
+    /// output goes into the terminal's own scrollback, like rlwrap, and
+    /// viri only draws the input and status rows below it
+    Inline,
+    /// viri draws the whole screen
+    Fullscreen,
+    /// the whole screen, on the alternate screen so the shell's is kept
+    Altscreen,
+}
+
+/// A cursor shape, as set with the DECSCUSR escape `ESC [ n SP q`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    /// whatever the terminal uses when nothing has changed it
+    Default = 0,
+    BlinkBlock = 1,
+    Block = 2,
+    BlinkUnderline = 3,
+    Underline = 4,
+    BlinkBar = 5,
+    Bar = 6,
+}
+
+static SHAPES: &[(&str, Shape)] = &[
+    ("default", Shape::Default),
+    ("blinkblock", Shape::BlinkBlock),
+    ("block", Shape::Block),
+    ("blinkunderline", Shape::BlinkUnderline),
+    ("underline", Shape::Underline),
+    ("blinkbar", Shape::BlinkBar),
+    ("bar", Shape::Bar),
+];
+
+impl Shape {
+    fn name(self) -> &'static str {
+        SHAPES
+            .iter()
+            .find(|(_, s)| *s == self)
+            .map_or("", |(n, _)| n)
+    }
+
+    pub fn escape(self) -> String {
+        format!("\x1b[{} q", self as u8)
+    }
+}
+
+/// The cursor shape in each mode; command covers output searches too.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CursorShapes {
+    pub normal: Shape,
+    pub insert: Shape,
+    pub replace: Shape,
+    pub command: Shape,
+}
+
+impl Default for CursorShapes {
+    fn default() -> Self {
+        CursorShapes {
+            normal: Shape::Block,
+            insert: Shape::Bar,
+            replace: Shape::Underline,
+            command: Shape::Bar,
+        }
+    }
+}
+
+/// Which inputs to leave out of the history, as in bash's `HISTCONTROL`.
+#[derive(Clone, Debug, Default)]
+pub struct HistControl {
+    /// skip an input identical to the one before it
+    pub ignoredups: bool,
+    /// remove older copies of an input when it is added again
+    pub erasedups: bool,
+    /// skip inputs starting with a space
+    pub ignorespace: bool,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Options {
+            timeoutlen: TIMEOUT_LEN,
+            histexpand: false,
+            histfile: None,
+            histcontrol: HistControl::default(),
+            histignore: Vec::new(),
+            prompt: None,
+            replaydelay: REPLAY_DELAY,
+            display: Display::Inline,
+            statusline: STATUSLINE.to_string(),
+            cursorshape: CursorShapes::default(),
+            syntax: String::new(),
+            keywords: Vec::new(),
+            autopairs: false,
+            multiline: true,
+            continuation: None,
+            editexec: false,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeoutlen)
+    }
+
+    pub fn replay_delay(&self) -> Duration {
+        Duration::from_millis(self.replaydelay)
+    }
+
+    /// Apply a single `:set` argument: `name`, `noname`, `name=value`,
+    /// `name+=value` or `name?`. Returns the text to show in the status
+    /// line, if any.
+    pub fn set(&mut self, arg: &str) -> Result<Option<String>> {
+        if let Some(name) = arg.strip_suffix('?') {
+            return Ok(Some(format!("{}={}", name, self.get(name)?)));
+        }
+
+        if let Some((name, value)) = arg.split_once("+=") {
+            match name {
+                "histignore" => self.histignore.push(Regex::new(value)?),
+                "keywords" => self.keywords.extend(list(value)),
+                _ => bail!("cannot append to option: {}", name),
+            }
+            return Ok(None);
+        }
+
+        match arg.split_once('=') {
+            Some(("timeoutlen", value)) => {
+                self.timeoutlen = value
+                    .parse()
+                    .map_err(|_| anyhow!("invalid number: {}", value))?;
+            }
+            Some(("replaydelay", value)) => {
+                self.replaydelay = value
+                    .parse()
+                    .map_err(|_| anyhow!("invalid number: {}", value))?;
+            }
+            Some(("display", value)) => {
+                self.display = match value {
+                    "inline" => Display::Inline,
+                    "fullscreen" => Display::Fullscreen,
+                    "altscreen" => Display::Altscreen,
+                    _ => bail!("invalid display: {}", value),
+                }
+            }
+            Some(("statusline", value)) => self.statusline = value.to_string(),
+            Some(("cursorshape", value)) => {
+                for part in value.split(',').filter(|p| !p.is_empty()) {
+                    let (modes, name) =
+                        part.split_once(':').ok_or_else(|| {
+                            anyhow!("invalid cursorshape: {}", part)
+                        })?;
+                    let shape = SHAPES
+                        .iter()
+                        .find(|(n, _)| *n == name)
+                        .map(|(_, s)| *s)
+                        .ok_or_else(|| anyhow!("invalid shape: {}", name))?;
+                    for mode in modes.split('-') {
+                        let shapes = &mut self.cursorshape;
+                        match mode {
+                            "n" => shapes.normal = shape,
+                            "i" => shapes.insert = shape,
+                            "r" => shapes.replace = shape,
+                            "c" => shapes.command = shape,
+                            _ => bail!("invalid cursorshape mode: {}", mode),
+                        }
+                    }
+                }
+            }
+            Some(("syntax", value)) => {
+                syntax::lexer(value)
+                    .ok_or_else(|| anyhow!("unknown syntax: {}", value))?;
+                self.syntax = value.to_string();
+            }
+            Some(("keywords", value)) => self.keywords = list(value).collect(),
+            Some(("continuation", "")) => self.continuation = None,
+            Some(("continuation", value)) => {
+                self.continuation = Some(Regex::new(value)?)
+            }
+            Some(("prompt", "")) => self.prompt = None,
+            Some(("prompt", value)) => self.prompt = Some(Regex::new(value)?),
+            Some(("histfile", "")) => self.histfile = None,
+            Some(("histfile", value)) => self.histfile = Some(expand_path(value)),
+            Some(("histcontrol", value)) => {
+                let mut control = HistControl::default();
+                for flag in value.split(',').filter(|f| !f.is_empty()) {
+                    match flag {
+                        "ignoredups" => control.ignoredups = true,
+                        "erasedups" => control.erasedups = true,
+                        "ignorespace" => control.ignorespace = true,
+                        "ignoreboth" => {
+                            control.ignoredups = true;
+                            control.ignorespace = true;
+                        }
+                        _ => bail!("invalid histcontrol: {}", flag),
+                    }
+                }
+                self.histcontrol = control;
+            }
+            Some(("histignore", "")) => self.histignore.clear(),
+            Some(("histignore", value)) => {
+                self.histignore = vec![Regex::new(value)?];
+            }
+            Some((name, _)) => bail!("unknown option: {}", name),
+            None => match arg {
+                "all" => return Ok(Some(self.show())),
+                "histexpand" => self.histexpand = true,
+                "nohistexpand" => self.histexpand = false,
+                "autopairs" => self.autopairs = true,
+                "noautopairs" => self.autopairs = false,
+                "multiline" => self.multiline = true,
+                "nomultiline" => self.multiline = false,
+                "editexec" => self.editexec = true,
+                "noeditexec" => self.editexec = false,
+                _ => return Ok(Some(format!("{}={}", arg, self.get(arg)?))),
+            },
+        }
+        Ok(None)
+    }
+
+    pub fn get(&self, name: &str) -> Result<String> {
+        Ok(match name {
+            "timeoutlen" => self.timeoutlen.to_string(),
+            "histexpand" => self.histexpand.to_string(),
+            "histfile" => self
+                .histfile
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            "histcontrol" => {
+                let control = &self.histcontrol;
+                [
+                    (control.ignoredups, "ignoredups"),
+                    (control.erasedups, "erasedups"),
+                    (control.ignorespace, "ignorespace"),
+                ]
+                .iter()
+                .filter(|(on, _)| *on)
+                .map(|(_, flag)| *flag)
+                .collect::<Vec<&str>>()
+                .join(",")
+            }
+            "histignore" => self
+                .histignore
+                .iter()
+                .map(|r| r.as_str())
+                .collect::<Vec<&str>>()
+                .join(" "),
+            "prompt" => self
+                .prompt
+                .as_ref()
+                .map(|r| r.as_str().to_string())
+                .unwrap_or_default(),
+            "replaydelay" => self.replaydelay.to_string(),
+            "display" => match self.display {
+                Display::Inline => "inline",
+                Display::Fullscreen => "fullscreen",
+                Display::Altscreen => "altscreen",
+            }
+            .to_string(),
+            "statusline" => self.statusline.clone(),
+            "cursorshape" => {
+                let shapes = &self.cursorshape;
+                [
+                    ("n", shapes.normal),
+                    ("i", shapes.insert),
+                    ("r", shapes.replace),
+                    ("c", shapes.command),
+                ]
+                .iter()
+                .map(|(mode, shape)| format!("{}:{}", mode, shape.name()))
+                .collect::<Vec<String>>()
+                .join(",")
+            }
+            "syntax" => self.syntax.clone(),
+            "keywords" => self.keywords.join(","),
+            "autopairs" => self.autopairs.to_string(),
+            "multiline" => self.multiline.to_string(),
+            "editexec" => self.editexec.to_string(),
+            "continuation" => self
+                .continuation
+                .as_ref()
+                .map(|r| r.as_str().to_string())
+                .unwrap_or_default(),
+            _ => bail!("unknown option: {}", name),
+        })
+    }
+
+    pub fn show(&self) -> String {
+        OPTIONS
+            .iter()
+            .map(|name| format!("{}={}", name, self.get(name).unwrap()))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// The items of a comma separated option value.
+fn list(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+/// `$VIRIRC`, or `~/.virirc` when it is not set
+pub fn rc_path() -> Option<PathBuf> {
+    match env::var_os("VIRIRC") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => env::var_os("HOME").map(|h| PathBuf::from(h).join(".virirc")),
+    }
+}
+
+/// Default `histfile` for `program`, under `$XDG_DATA_HOME/viri` or
+/// `~/.local/share/viri`.
+pub fn histfile(program: &str) -> Option<PathBuf> {
+    let data = match env::var_os("XDG_DATA_HOME") {
+        Some(data) => PathBuf::from(data),
+        None => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(data.join("viri").join(format!("{}_history", program)))
+}
+
+/// Expand a leading `~/` to the home directory.
+pub fn expand_path(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
 // use tokio::{
 //     fs::read,
 // };
@@ -36,11 +426,18 @@ pub fn get_config(_con_f: &str) -> std::io::Result<HashMap<String, String>> {
     config.insert("^".to_string(), "CursorLeftAll".to_string());
     config.insert("i".to_string(), "Insert".to_string());
     config.insert("I".to_string(), "InsertStart".to_string());
+    config.insert("R".to_string(), "Replace".to_string());
     config.insert("a".to_string(), "Append".to_string());
     config.insert("A".to_string(), "AppendEnd".to_string());
     config.insert("C".to_string(), "ClearAfterCursor".to_string());
+    config.insert("v".to_string(), "EditInput".to_string());
+    config.insert("%".to_string(), "CursorMatch".to_string());
+    config.insert("d%".to_string(), "DeleteMatch".to_string());
+    config.insert("c%".to_string(), "ChangeMatch".to_string());
     config.insert("s".to_string(), "DeletePosInsert".to_string());
     config.insert("S".to_string(), "DeleteLineInsert".to_string());
+    config.insert("n".to_string(), "SearchNext".to_string());
+    config.insert("N".to_string(), "SearchPrev".to_string());
     config.insert("q".to_string(), "Quit".to_string());
     Ok(config)
 }
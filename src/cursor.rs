@@ -1,5 +1,8 @@
 use std::fmt::{self, Formatter};
 
+/// Brackets that `%` and the matching bracket highlight pair up.
+pub const PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
 #[derive(Debug, PartialEq)]
 pub struct Cursor {
     before: String,
@@ -21,8 +24,23 @@ impl Cursor {
         }
     }
 
-    pub fn pos(&self) -> usize {
-        self.before.len() + 1
+    /// the text before the cursor
+    pub fn before(&self) -> &str {
+        &self.before
+    }
+
+    /// the cursor's position, in characters
+    pub fn index(&self) -> usize {
+        self.before.chars().count()
+    }
+
+    /// the character under the cursor
+    pub fn current(&self) -> Option<char> {
+        self.after.chars().next_back()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.before.is_empty() && self.after.is_empty()
     }
 
     pub fn insert(&mut self, c: char) {
@@ -37,6 +55,12 @@ impl Cursor {
         self.after.pop();
     }
 
+    /// Type `c` over the character under the cursor.
+    pub fn replace(&mut self, c: char) {
+        self.after.pop();
+        self.before.push(c);
+    }
+
     /*
      * Normal mode movements
      */
@@ -53,7 +77,7 @@ impl Cursor {
     }
 
     pub fn left_word(&mut self) {
-        if self.before.len() == 0 {
+        if self.before.is_empty() {
             return;
         }
 
@@ -73,12 +97,13 @@ impl Cursor {
     }
 
     pub fn right_word(&mut self) {
-        let pos = match self.after.trim_end().rfind(|c: char| {
-            c.is_whitespace() || ['.', '\\', '/', '.', ','].contains(&c)
-        }) {
-            Some(p) => p,
-            None => 1,
-        };
+        let pos = self
+            .after
+            .trim_end()
+            .rfind(|c: char| {
+                c.is_whitespace() || ['.', '\\', '/', '.', ','].contains(&c)
+            })
+            .unwrap_or(1);
         self.before
             .push_str(&self.after.drain(pos..).rev().collect::<String>());
     }
@@ -91,6 +116,90 @@ impl Cursor {
         self.after = String::new();
     }
 
+    /// Where the bracket at `at` is closed or opened, if it is a bracket.
+    pub fn partner(&self, at: usize) -> Option<usize> {
+        let chars: Vec<char> = self.to_string().chars().collect();
+        let c = *chars.get(at)?;
+        let (forward, (open, close)) =
+            PAIRS.iter().find_map(|&pair| {
+                match (c == pair.0, c == pair.1) {
+                    (true, _) => Some((true, pair)),
+                    (_, true) => Some((false, pair)),
+                    _ => None,
+                }
+            })?;
+        let mut depth = 0;
+        let mut i = at;
+        loop {
+            match chars[i] {
+                b if b == open => depth += 1,
+                b if b == close => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return Some(i);
+            }
+            i = match forward {
+                true if i + 1 < chars.len() => i + 1,
+                false if i > 0 => i - 1,
+                _ => return None,
+            };
+        }
+    }
+
+    /// Where `%` goes: the partner of the first bracket at or after the
+    /// cursor.
+    pub fn bracket_target(&self) -> Option<usize> {
+        self.to_string()
+            .chars()
+            .enumerate()
+            .skip(self.index())
+            .find_map(|(i, _)| self.partner(i))
+    }
+
+    /// Move to the line above or below, keeping the column where that line
+    /// is long enough. Returns false when there is no such line.
+    pub fn move_line(&mut self, down: bool) -> bool {
+        let text: Vec<char> = self.to_string().chars().collect();
+        let mut starts = vec![0];
+        starts.extend(
+            text.iter()
+                .enumerate()
+                .filter(|(_, c)| **c == '\n')
+                .map(|(i, _)| i + 1),
+        );
+        let index = self.index();
+        let line = starts.iter().rposition(|&s| s <= index).unwrap_or(0);
+        let target = match down {
+            true if line + 1 < starts.len() => line + 1,
+            false if line > 0 => line - 1,
+            _ => return false,
+        };
+        let end = starts.get(target + 1).map_or(text.len(), |&s| s - 1);
+        self.move_to((starts[target] + index - starts[line]).min(end));
+        true
+    }
+
+    /// Put the cursor before the character at `index`.
+    pub fn move_to(&mut self, index: usize) {
+        self.left_all();
+        for _ in 0..index {
+            self.right_char();
+        }
+    }
+
+    /// Remove the characters from `start` to `end`, leaving the cursor
+    /// where they were.
+    pub fn delete_range(&mut self, start: usize, end: usize) {
+        let text: Vec<char> = self.to_string().chars().collect();
+        let end = end.min(text.len());
+        let start = start.min(end);
+        *self = Cursor::from(
+            text[..start].iter().collect(),
+            text[end..].iter().collect(),
+        );
+    }
+
     pub fn right_all(&mut self) {
         self.before
             .push_str(&self.after.drain(..).rev().collect::<String>());
@@ -161,4 +270,40 @@ mod tests {
             ),
         );
     }
+
+    #[test]
+    pub fn replace_chars() {
+        let mut cursor = Cursor::from("se".to_string(), "lect".to_string());
+        "xyz12".chars().for_each(|c| cursor.replace(c));
+        assert_eq!(cursor.to_string(), "sexyz12");
+        assert!(cursor.after.is_empty());
+    }
+
+    #[test]
+    pub fn match_brackets() {
+        let mut cursor =
+            Cursor::from("f".to_string(), "(a[1], {b})) x".to_string());
+        assert_eq!(cursor.partner(1), Some(11));
+        assert_eq!(cursor.partner(11), Some(1));
+        assert_eq!(cursor.partner(12), None);
+        assert_eq!(cursor.partner(2), None);
+        assert_eq!(cursor.bracket_target(), Some(11));
+        cursor.move_to(4);
+        assert_eq!(cursor.bracket_target(), Some(3));
+        cursor.delete_range(3, 6);
+        assert_eq!(cursor.to_string(), "f(a, {b})) x");
+        assert_eq!(cursor.current(), Some(','));
+    }
+
+    #[test]
+    pub fn move_lines() {
+        let mut cursor =
+            Cursor::from("def f():\n    ret".to_string(), "urn 1".to_string());
+        assert!(cursor.move_line(false));
+        assert_eq!(cursor.before(), "def f()");
+        assert!(!cursor.move_line(false));
+        assert!(cursor.move_line(true));
+        assert_eq!(cursor.before(), "def f():\n    ret");
+        assert!(!cursor.move_line(true));
+    }
 }
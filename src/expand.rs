@@ -0,0 +1,176 @@
+use super::history::History;
+use anyhow::{anyhow, bail, Result};
+
+/// Expand bash-style history designators in `line` against the inputs in
+/// `history`:
+///
+/// * `!!` the last input
+/// * `!n` / `!-n` the n-th input, or the n-th most recent one
+/// * `!prefix` the last input starting with `prefix`
+/// * `!$` the last word of the last input
+/// * `!*` every word but the first of the last input
+///
+/// As in bash, only a `!` starting a word is a designator: `\!`, a `!`
+/// inside single quotes, after anything but whitespace, or followed by
+/// whitespace, `=`, `(`, `"`, `)`, `;` or the end of the line is left
+/// alone. Returns `None` when there was nothing to expand.
+pub fn expand(line: &str, history: &History) -> Result<Option<String>> {
+    let mut out = String::new();
+    let mut changed = false;
+    let mut quoted = false;
+    let mut prev = None;
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let starts_word = prev.is_none_or(char::is_whitespace);
+        prev = Some(c);
+        match c {
+            '\'' => {
+                quoted = !quoted;
+                out.push(c);
+            }
+            '\\' if chars.peek().map(|(_, n)| *n) == Some('!') => {
+                out.push('!');
+                changed = true;
+                chars.next();
+            }
+            '!' if !quoted && starts_word => {
+                let rest = &line[i + 1..];
+                let (text, used) = match rest.chars().next() {
+                    None => (None, 0),
+                    Some(n) if n.is_whitespace() || "=(\");".contains(n) => {
+                        (None, 0)
+                    }
+                    Some('!') => (Some(last(history)?.to_string()), 1),
+                    Some('$') => (Some(last_word(history)?), 1),
+                    Some('*') => (Some(arguments(history)?), 1),
+                    Some(_) => {
+                        let end = rest
+                            .find(|c: char| c.is_whitespace())
+                            .unwrap_or(rest.len());
+                        (Some(designator(&rest[..end], history)?), end)
+                    }
+                };
+                match text {
+                    Some(text) => {
+                        out.push_str(&text);
+                        changed = true;
+                        for _ in rest[..used].chars() {
+                            chars.next();
+                        }
+                    }
+                    None => out.push(c),
+                }
+            }
+            c => out.push(c),
+        }
+    }
+
+    Ok(if changed { Some(out) } else { None })
+}
+
+fn last(history: &History) -> Result<&str> {
+    history
+        .inputs()
+        .next_back()
+        .ok_or_else(|| anyhow!("!!: event not found"))
+}
+
+fn last_word(history: &History) -> Result<String> {
+    Ok(last(history)?
+        .to_string()
+        .split_whitespace()
+        .last()
+        .unwrap_or("")
+        .to_string())
+}
+
+fn arguments(history: &History) -> Result<String> {
+    Ok(last(history)?
+        .to_string()
+        .split_whitespace()
+        .skip(1)
+        .collect::<Vec<&str>>()
+        .join(" "))
+}
+
+fn designator(word: &str, history: &History) -> Result<String> {
+    let found = match word.parse::<isize>() {
+        Ok(n) if n > 0 => history.inputs().nth(n as usize - 1),
+        Ok(n) if n < 0 => history.inputs().rev().nth((-n) as usize - 1),
+        Ok(_) => None,
+        Err(_) => history.inputs().rev().find(|i| i.starts_with(word)),
+    };
+    match found {
+        Some(c) => Ok(c.to_string()),
+        None => bail!("!{}: event not found", word),
+    }
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Options, cursor::Cursor, history::Source};
+
+    fn history() -> History {
+        let mut history = History::new();
+        for line in &["select 1", "\\d users", "select * from users"] {
+            history.add_input(line, &Options::new()).unwrap();
+            history.push(Source::ChildInput(Cursor::from(
+                line.to_string(),
+                String::new(),
+            )));
+            history.push(Source::ChildOutput(Cursor::new()));
+        }
+        history
+    }
+
+    fn expanded(line: &str) -> String {
+        expand(line, &history()).unwrap().unwrap()
+    }
+
+    #[test]
+    pub fn expand_last() {
+        assert_eq!(expanded("!!"), "select * from users");
+        assert_eq!(expanded("explain !!"), "explain select * from users");
+    }
+
+    #[test]
+    pub fn expand_numbered() {
+        assert_eq!(expanded("!1"), "select 1");
+        assert_eq!(expanded("!-2"), "\\d users");
+        assert!(expand("!9", &history()).is_err());
+    }
+
+    #[test]
+    pub fn expand_prefix() {
+        assert_eq!(expanded("!sel"), "select * from users");
+        assert_eq!(expanded("!\\d"), "\\d users");
+    }
+
+    #[test]
+    pub fn expand_words() {
+        assert_eq!(expanded("drop table !$"), "drop table users");
+        assert_eq!(expanded("echo !*"), "echo * from users");
+    }
+
+    #[test]
+    pub fn expand_nothing() {
+        let history = history();
+        assert_eq!(expand("select 1 != 2", &history).unwrap(), None);
+        assert_eq!(expand("select '!!'", &history).unwrap(), None);
+        assert_eq!(expand("wow!", &history).unwrap(), None);
+        assert_eq!(expand("print(\"hi!\")", &history).unwrap(), None);
+        assert_eq!(expand("if (!done) go()", &history).unwrap(), None);
+        assert_eq!(expand("x=!sel", &history).unwrap(), None);
+        assert_eq!(expand("echo !\"a\" !;", &history).unwrap(), None);
+        assert_eq!(
+            expand("echo \\!!", &history).unwrap(),
+            Some("echo !!".to_string())
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use super::history::Meta;
+use anyhow::{bail, Result};
+use std::{str::FromStr, time::UNIX_EPOCH};
+
+/// Ways to write out a session.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// one JSON object per entry with its kind, text and metadata
+    Json,
+    /// the transcript as it read on screen, inputs marked with `> `
+    Text,
+    /// only the inputs, one per line, ready to be replayed
+    Script,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "json" => Format::Json,
+            "text" => Format::Text,
+            "script" => Format::Script,
+            _ => bail!("unknown export format: {}", s),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Input,
+    Output,
+    Error,
+}
+
+/// Render `entries`, oldest first, in `format`.
+pub fn render<'a, I>(entries: I, format: Format) -> String
+where
+    I: Iterator<Item = (Kind, String, &'a Meta)>,
+{
+    let mut out = String::new();
+    for (kind, text, meta) in entries {
+        match (format, kind) {
+            (Format::Json, _) => {
+                out.push_str(&json(kind, &text, meta));
+                out.push('\n');
+            }
+            (Format::Text, Kind::Input) => {
+                for line in text.lines() {
+                    out.push_str("> ");
+                    out.push_str(line);
+                    out.push('\n');
+                }
+            }
+            (Format::Text, Kind::Output)
+            | (Format::Text, Kind::Error)
+            | (Format::Script, Kind::Input) => {
+                out.push_str(&text);
+                out.push('\n');
+            }
+            (Format::Script, _) => {}
+        }
+    }
+    out
+}
+
+fn json(kind: Kind, text: &str, meta: &Meta) -> String {
+    let mut fields = vec![
+        format!(
+            "\"kind\":\"{}\"",
+            match kind {
+                Kind::Input => "input",
+                Kind::Output => "output",
+                Kind::Error => "error",
+            }
+        ),
+        format!("\"text\":{}", json_string(text)),
+    ];
+    if let Some(time) = meta.time {
+        fields.push(format!("\"timestamp\":\"{}\"", rfc3339(time)));
+    }
+    if let Some(session) = meta.session {
+        fields.push(format!("\"session\":{}", session));
+    }
+    if let Some(cwd) = &meta.cwd {
+        fields.push(format!("\"cwd\":{}", json_string(&cwd.to_string_lossy())));
+    }
+    format!("{{{}}}", fields.join(","))
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                out.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// UTC time as `YYYY-MM-DDThh:mm:ssZ`.
+fn rfc3339(time: std::time::SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // days since the epoch to a civil date, after Howard Hinnant's
+    // `civil_from_days`
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{path::PathBuf, time::Duration};
+
+    fn session() -> Vec<(Kind, String, Meta)> {
+        let meta = Meta {
+            time: Some(UNIX_EPOCH + Duration::from_secs(1700000000)),
+            session: Some(7),
+            cwd: Some(PathBuf::from("/tmp")),
+        };
+        vec![
+            (Kind::Input, "select \"a\"".to_string(), meta.clone()),
+            (Kind::Output, "a".to_string(), Meta::default()),
+        ]
+    }
+
+    fn rendered(format: Format) -> String {
+        let session = session();
+        render(session.iter().map(|(k, t, m)| (*k, t.clone(), m)), format)
+    }
+
+    #[test]
+    pub fn export_json() {
+        assert_eq!(
+            rendered(Format::Json),
+            "{\"kind\":\"input\",\"text\":\"select \\\"a\\\"\",\
+             \"timestamp\":\"2023-11-14T22:13:20Z\",\"session\":7,\
+             \"cwd\":\"/tmp\"}\n\
+             {\"kind\":\"output\",\"text\":\"a\"}\n"
+        );
+    }
+
+    #[test]
+    pub fn export_text_and_script() {
+        assert_eq!(rendered(Format::Text), "> select \"a\"\na\n");
+        assert_eq!(rendered(Format::Script), "select \"a\"\n");
+    }
+}
@@ -0,0 +1,358 @@
+use super::{
+    ansi::{Cell, Color, Style},
+    substitute,
+    syntax::Token,
+};
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+
+/// Names of the highlight groups, in the order `:highlight` lists them.
+static GROUPS: &[&str] = &[
+    "Input",
+    "Output",
+    "Error",
+    "Prompt",
+    "StatusLine",
+    "Keyword",
+    "String",
+    "Number",
+    "Comment",
+    "MatchParen",
+];
+
+/// Names of the first eight colors; `bright` before one is the next eight.
+static COLORS: &[&str] = &[
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Styles for the parts of the screen, set with `:highlight`, and the
+/// rules for output added with `:match`.
+#[derive(Clone, Debug)]
+pub struct Highlights {
+    /// inputs echoed into the transcript
+    pub input: Style,
+    pub output: Style,
+    /// lines the program wrote to stderr
+    pub error: Style,
+    /// what `prompt` matches in the output
+    pub prompt: Style,
+    pub statusline: Style,
+    /// the input line's tokens, as its `syntax` lexer finds them
+    pub keyword: Style,
+    pub string: Style,
+    pub number: Style,
+    pub comment: Style,
+    /// the partner of the bracket at the cursor
+    pub matchparen: Style,
+    pub rules: Vec<Rule>,
+}
+
+/// Lay `style` over whatever `pattern` matches in the program's output.
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub pattern: Regex,
+    pub style: Style,
+}
+
+impl Default for Highlights {
+    fn default() -> Self {
+        Highlights {
+            input: Style {
+                bold: true,
+                ..Style::default()
+            },
+            output: Style::default(),
+            error: Style {
+                fg: Some(Color::Index(1)),
+                ..Style::default()
+            },
+            prompt: Style::default(),
+            statusline: Style {
+                inverse: true,
+                ..Style::default()
+            },
+            keyword: Style {
+                fg: Some(Color::Index(4)),
+                bold: true,
+                ..Style::default()
+            },
+            string: Style {
+                fg: Some(Color::Index(2)),
+                ..Style::default()
+            },
+            number: Style {
+                fg: Some(Color::Index(6)),
+                ..Style::default()
+            },
+            comment: Style {
+                fg: Some(Color::Index(8)),
+                ..Style::default()
+            },
+            matchparen: Style {
+                bg: Some(Color::Index(6)),
+                ..Style::default()
+            },
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl Highlights {
+    /// Apply `:highlight {group} {attributes}`, replacing the group's
+    /// style. Without attributes the group is shown, and without a group
+    /// all of them are.
+    pub fn set(&mut self, args: &[&str]) -> Result<Option<String>> {
+        match args {
+            [] => Ok(Some(
+                GROUPS
+                    .iter()
+                    .map(|name| self.show(name))
+                    .collect::<Result<Vec<String>>>()?
+                    .join("\n"),
+            )),
+            [name] => Ok(Some(self.show(name)?)),
+            [name, attrs @ ..] => {
+                *self.group(index(name)?) = parse_style(attrs)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Apply `:match /{pattern}/ {attributes}`, adding a rule for output
+    /// lines; any delimiter works, as for `:s`. Without a pattern the rules
+    /// are shown, and `:match none` removes them all.
+    pub fn add_rule(&mut self, spec: &str) -> Result<Option<String>> {
+        let delim = match spec.chars().next() {
+            None => {
+                return Ok(Some(
+                    self.rules
+                        .iter()
+                        .map(|r| {
+                            format!("/{}/ {}", r.pattern, describe(&r.style))
+                        })
+                        .collect::<Vec<String>>()
+                        .join("\n"),
+                ))
+            }
+            Some(_) if spec == "none" => {
+                self.rules.clear();
+                return Ok(None);
+            }
+            Some(c) if c.is_alphanumeric() || c.is_whitespace() => {
+                bail!("usage: match /pattern/ {{attributes}}")
+            }
+            Some(c) => c,
+        };
+        let (pattern, attrs) =
+            match &substitute::split(&spec[delim.len_utf8()..], delim)[..] {
+                [pattern, attrs] if !pattern.is_empty() => {
+                    (pattern.clone(), attrs.clone())
+                }
+                [_, _] => bail!("empty pattern"),
+                _ => bail!("usage: match /pattern/ {{attributes}}"),
+            };
+        let attrs: Vec<&str> = attrs.split_whitespace().collect();
+        self.rules.push(Rule {
+            pattern: Regex::new(&pattern)?,
+            style: parse_style(&attrs)?,
+        });
+        Ok(None)
+    }
+
+    fn show(&mut self, name: &str) -> Result<String> {
+        let group = index(name)?;
+        let style = *self.group(group);
+        Ok(format!("{:<12}{}", GROUPS[group], describe(&style)))
+    }
+
+    fn group(&mut self, index: usize) -> &mut Style {
+        match index {
+            0 => &mut self.input,
+            1 => &mut self.output,
+            2 => &mut self.error,
+            3 => &mut self.prompt,
+            4 => &mut self.statusline,
+            5 => &mut self.keyword,
+            6 => &mut self.string,
+            7 => &mut self.number,
+            8 => &mut self.comment,
+            _ => &mut self.matchparen,
+        }
+    }
+
+    /// The style for a token of the input line.
+    pub fn token(&self, token: Token) -> Style {
+        match token {
+            Token::Plain => Style::default(),
+            Token::Keyword => self.keyword,
+            Token::String => self.string,
+            Token::Number => self.number,
+            Token::Comment => self.comment,
+        }
+    }
+}
+
+/// Where the group `name`, in any case, is in `GROUPS`.
+fn index(name: &str) -> Result<usize> {
+    GROUPS
+        .iter()
+        .position(|g| g.eq_ignore_ascii_case(name))
+        .ok_or_else(|| anyhow!("no such highlight group: {}", name))
+}
+
+/// Parse style attributes: `bold`, `dim`, `italic`, `underline`,
+/// `inverse`, `fg={color}` and `bg={color}`, or `none` for the plain style.
+pub fn parse_style(attrs: &[&str]) -> Result<Style> {
+    let mut style = Style::default();
+    for attr in attrs {
+        match attr.split_once('=') {
+            Some(("fg", color)) => style.fg = Some(parse_color(color)?),
+            Some(("bg", color)) => style.bg = Some(parse_color(color)?),
+            Some(_) => bail!("invalid attribute: {}", attr),
+            None => match *attr {
+                "none" => style = Style::default(),
+                "bold" => style.bold = true,
+                "dim" => style.dim = true,
+                "italic" => style.italic = true,
+                "underline" => style.underline = true,
+                "inverse" => style.inverse = true,
+                _ => bail!("invalid attribute: {}", attr),
+            },
+        }
+    }
+    Ok(style)
+}
+
+/// Parse a color name such as `red` or `brightred`, a number from the 256
+/// color palette or a truecolor `#rrggbb`.
+pub fn parse_color(color: &str) -> Result<Color> {
+    let invalid = || anyhow!("invalid color: {}", color);
+    if let Some(hex) = color.strip_prefix('#') {
+        let rgb = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+        if hex.len() != 6 {
+            return Err(invalid());
+        }
+        return Ok(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+    }
+    if let Ok(n) = color.parse() {
+        return Ok(Color::Index(n));
+    }
+    let (bright, name) = match color.strip_prefix("bright") {
+        Some(name) => (8, name),
+        None => (0, color),
+    };
+    COLORS
+        .iter()
+        .position(|c| *c == name)
+        .map(|n| Color::Index(bright + n as u8))
+        .ok_or_else(invalid)
+}
+
+/// Attributes that `parse_style` reads back as `style`.
+pub fn describe(style: &Style) -> String {
+    let mut attrs: Vec<String> = [
+        (style.bold, "bold"),
+        (style.dim, "dim"),
+        (style.italic, "italic"),
+        (style.underline, "underline"),
+        (style.inverse, "inverse"),
+    ]
+    .iter()
+    .filter(|(on, _)| *on)
+    .map(|(_, attr)| attr.to_string())
+    .collect();
+    for (name, color) in [("fg", style.fg), ("bg", style.bg)] {
+        if let Some(color) = color {
+            attrs.push(format!("{}={}", name, color_name(color)));
+        }
+    }
+    if attrs.is_empty() {
+        return "none".to_string();
+    }
+    attrs.join(" ")
+}
+
+fn color_name(color: Color) -> String {
+    match color {
+        Color::Index(n) if n < 8 => COLORS[n as usize].to_string(),
+        Color::Index(n) if n < 16 => {
+            format!("bright{}", COLORS[n as usize - 8])
+        }
+        Color::Index(n) => n.to_string(),
+        Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+    }
+}
+
+/// Lay `style` over the cells `pattern` matches.
+pub fn paint(cells: &mut [Cell], pattern: &Regex, style: Style) {
+    let text: String = cells.iter().map(|(c, _)| c).collect();
+    let chars: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+    for m in pattern.find_iter(&text) {
+        let start = chars.partition_point(|&i| i < m.start());
+        let end = chars.partition_point(|&i| i < m.end());
+        for (_, cell) in &mut cells[start..end] {
+            *cell = style.over(*cell);
+        }
+    }
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn parse_colors() {
+        assert_eq!(parse_color("red").unwrap(), Color::Index(1));
+        assert_eq!(parse_color("brightwhite").unwrap(), Color::Index(15));
+        assert_eq!(parse_color("208").unwrap(), Color::Index(208));
+        assert_eq!(parse_color("#ff8000").unwrap(), Color::Rgb(255, 128, 0));
+        assert!(parse_color("#ff80").is_err());
+        assert!(parse_color("mauve").is_err());
+    }
+
+    #[test]
+    pub fn set_groups() {
+        let mut highlights = Highlights::default();
+        highlights
+            .set(&["error", "fg=brightred", "bg=#102030", "bold"])
+            .unwrap();
+        assert_eq!(
+            highlights.set(&["Error"]).unwrap().unwrap(),
+            "Error       bold fg=brightred bg=#102030"
+        );
+        highlights.set(&["Input", "none"]).unwrap();
+        assert_eq!(highlights.input, Style::default());
+        assert!(highlights.set(&["Todo", "bold"]).is_err());
+        assert!(highlights.set(&["Output", "blink"]).is_err());
+    }
+
+    #[test]
+    pub fn match_rules() {
+        let mut highlights = Highlights::default();
+        highlights.add_rule("/ERROR:/ fg=red bold").unwrap();
+        highlights.add_rule(r"#\d+(\.\d+)?# fg=cyan").unwrap();
+        highlights.add_rule("|a/b| underline").unwrap();
+        assert_eq!(
+            highlights.add_rule("").unwrap().unwrap(),
+            "/ERROR:/ bold fg=red\n/\\d+(\\.\\d+)?/ fg=cyan\n/a/b/ underline"
+        );
+        assert!(highlights.add_rule("/x/ blink").is_err());
+        assert!(highlights.add_rule("// bold").is_err());
+        assert!(highlights.add_rule("x bold").is_err());
+
+        let mut cells = crate::ansi::parse("ERROR: 42");
+        for rule in &highlights.rules {
+            paint(&mut cells, &rule.pattern, rule.style);
+        }
+        assert!(cells[0].1.bold && cells[5].1.fg == Some(Color::Index(1)));
+        assert_eq!(cells[6].1, Style::default());
+        assert_eq!(cells[8].1.fg, Some(Color::Index(6)));
+
+        highlights.add_rule("none").unwrap();
+        assert!(highlights.rules.is_empty());
+    }
+}
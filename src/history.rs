@@ -0,0 +1,829 @@
+use super::{config::Options, cursor::Cursor, layout};
+use anyhow::Result;
+use std::{
+    collections::HashSet,
+    env,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone, Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum Source {
+    ChildInput(Cursor),
+    ChildOutput(Cursor),
+    /// a line the program wrote to stderr
+    ChildError(Cursor),
+}
+
+/// When, in which viri session and from which directory an input was
+/// recorded. Outputs, and inputs read from plain history files, have none
+/// of it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Meta {
+    pub time: Option<SystemTime>,
+    pub session: Option<u32>,
+    /// viri's own working directory, which the program may have left
+    pub cwd: Option<PathBuf>,
+}
+
+impl Meta {
+    pub fn now() -> Self {
+        Meta {
+            time: Some(SystemTime::now()),
+            session: Some(process::id()),
+            cwd: env::current_dir().ok(),
+        }
+    }
+
+    /// How long ago the entry was recorded, e.g. `5m ago`.
+    pub fn ago(&self) -> String {
+        let secs = match self.time.map(|t| t.elapsed()) {
+            Some(Ok(elapsed)) => elapsed.as_secs(),
+            Some(Err(_)) => 0,
+            None => return "-".to_string(),
+        };
+        match secs {
+            0..=59 => format!("{}s ago", secs),
+            60..=3599 => format!("{}m ago", secs / 60),
+            3600..=86399 => format!("{}h ago", secs / 3600),
+            _ => format!("{}d ago", secs / 86400),
+        }
+    }
+}
+
+/// An input remembered for recall.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Input {
+    pub text: String,
+    pub meta: Meta,
+}
+
+impl Input {
+    /// Parse a line of a history file. Besides plain lines this reads zsh's
+    /// extended `: {time}:{duration};{text}` and viri's own
+    /// `: {time}:{session}:{cwd};{text}`, where `%` and `;` in the
+    /// directory are escaped as `%25` and `%3B`.
+    pub fn parse(line: &str) -> Self {
+        Input::parse_extended(line).unwrap_or_else(|| Input {
+            text: line.to_string(),
+            meta: Meta::default(),
+        })
+    }
+
+    fn parse_extended(line: &str) -> Option<Self> {
+        let (time, rest) = line.strip_prefix(": ")?.split_once(':')?;
+        let time = UNIX_EPOCH + Duration::from_secs(time.parse().ok()?);
+        let (fields, text) = rest.split_once(';')?;
+        let meta = match fields.split_once(':') {
+            Some((session, cwd)) => Meta {
+                time: Some(time),
+                session: Some(session.parse().ok()?),
+                cwd: Some(cwd)
+                    .filter(|cwd| !cwd.is_empty())
+                    .map(|cwd| PathBuf::from(unescape(cwd))),
+            },
+            None => {
+                fields.parse::<u64>().ok()?;
+                Meta {
+                    time: Some(time),
+                    ..Meta::default()
+                }
+            }
+        };
+        Some(Input {
+            text: text.to_string(),
+            meta,
+        })
+    }
+}
+
+impl std::fmt::Display for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // multi-line inputs continue with a trailing backslash, as in zsh,
+        // and backslashes that really end a line are doubled
+        let text = self
+            .text
+            .split('\n')
+            .map(|line| {
+                let trimmed = line.trim_end_matches('\\');
+                format!("{}{}", line, &line[trimmed.len()..])
+            })
+            .collect::<Vec<String>>()
+            .join("\\\n");
+        let secs = match self.meta.time.map(|t| t.duration_since(UNIX_EPOCH)) {
+            Some(Ok(since)) => since.as_secs(),
+            _ => return write!(f, "{}", text),
+        };
+        match self.meta.session {
+            Some(session) => {
+                let cwd = self.meta.cwd.as_ref().map(|c| c.to_string_lossy());
+                write!(
+                    f,
+                    ": {}:{}:{};{}",
+                    secs,
+                    session,
+                    escape(&cwd.unwrap_or_default()),
+                    text
+                )
+            }
+            None => write!(f, ": {}:0;{}", secs, text),
+        }
+    }
+}
+
+fn escape(cwd: &str) -> String {
+    cwd.replace('%', "%25").replace(';', "%3B")
+}
+
+fn unescape(cwd: &str) -> String {
+    cwd.replace("%3B", ";").replace("%25", "%")
+}
+
+/// The session transcript, plus a separate index of every input worth
+/// recalling: those sent this session that passed the `histcontrol` and
+/// `histignore` policies, and any loaded from `histfile`.
+#[derive(Clone, Debug)]
+pub struct History {
+    history: Vec<(Source, Meta)>,
+    /// screen rows each transcript entry takes at `width` columns, worked
+    /// out once as it is pushed
+    rows: Vec<usize>,
+    total: usize,
+    width: usize,
+    inputs: Vec<Input>,
+    /// position in `inputs` of the recalled line, `None` while on the
+    /// scratch line below the newest input
+    index: Option<usize>,
+    /// text before the cursor when navigation started; only inputs
+    /// starting with it are recalled
+    prefix: String,
+    /// the unsent line being edited when navigation started
+    scratch: Option<Cursor>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        let history = Vec::with_capacity(100);
+        History {
+            history,
+            rows: Vec::new(),
+            total: 0,
+            width: 80,
+            inputs: Vec::new(),
+            index: None,
+            prefix: String::new(),
+            scratch: None,
+        }
+    }
+
+    /// Add to the transcript. Inputs are recalled only once `add_input`ed.
+    pub fn push(&mut self, value: Source) {
+        let meta = match value {
+            Source::ChildInput(_) => Meta::now(),
+            _ => Meta::default(),
+        };
+        let rows = layout::entry_height(&value, self.width);
+        self.rows.push(rows);
+        self.total += rows;
+        self.history.push((value, meta));
+    }
+
+    /// Wrap the transcript at `width` columns from now on.
+    pub fn set_width(&mut self, width: usize) {
+        if width == self.width {
+            return;
+        }
+        self.width = width;
+        self.rows = self
+            .history
+            .iter()
+            .map(|(source, _)| layout::entry_height(source, width))
+            .collect();
+        self.total = self.rows.iter().sum();
+    }
+
+    /// Screen rows the whole transcript takes.
+    pub fn rows(&self) -> usize {
+        self.total
+    }
+
+    /// Screen rows of the transcript below the start of the entry at
+    /// `index`.
+    pub fn rows_from(&self, index: usize) -> usize {
+        self.rows.get(index..).map_or(0, |rows| rows.iter().sum())
+    }
+
+    /// The number of entries that start above the last `rows` screen rows
+    /// of the transcript.
+    pub fn entries_above(&self, rows: usize) -> usize {
+        let mut below = 0;
+        let mut index = self.rows.len();
+        while index > 0 && below + self.rows[index - 1] <= rows {
+            index -= 1;
+            below += self.rows[index];
+        }
+        index
+    }
+
+    /// Remember an input for recall, applying the history policies in
+    /// `options` and keeping `histfile` up to date.
+    pub fn add_input(&mut self, line: &str, options: &Options) -> Result<()> {
+        if !self.keep(line, options) {
+            return Ok(());
+        }
+
+        let before = self.inputs.len();
+        if options.histcontrol.erasedups {
+            self.inputs.retain(|i| i.text != line);
+        }
+        self.inputs.push(Input {
+            text: line.to_string(),
+            meta: Meta::now(),
+        });
+
+        let input = self.inputs.last().unwrap();
+        match &options.histfile {
+            Some(path) if self.inputs.len() <= before => {
+                rewrite(path, |saved| {
+                    saved.retain(|i| i.text != input.text);
+                    saved.push(input.clone());
+                })
+            }
+            Some(path) => append(path, input),
+            None => Ok(()),
+        }
+    }
+
+    /// Load inputs saved in `path`, applying the current policies to them.
+    /// The file itself is left alone, so another program or a later config
+    /// can still have what these policies filter out.
+    pub fn load(&mut self, path: &Path, options: &Options) -> Result<()> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        for line in records(&text).iter().map(|l| Input::parse(l)) {
+            if self.keep(&line.text, options) {
+                if options.histcontrol.erasedups {
+                    self.inputs.retain(|i| i.text != line.text);
+                }
+                self.inputs.push(line);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add inputs from another program's history ahead of the existing
+    /// ones, keeping their order. Inputs already in the history, repeats
+    /// within `imported` (the newest copy is kept) and anything the
+    /// policies ignore are left out. Returns how many were added.
+    pub fn import(
+        &mut self,
+        imported: Vec<Input>,
+        options: &Options,
+    ) -> Result<usize> {
+        let mut seen: HashSet<String> =
+            self.inputs.iter().map(|i| i.text.clone()).collect();
+        let mut added: Vec<Input> = imported
+            .into_iter()
+            .rev()
+            .filter(|i| {
+                !ignored(&i.text, options) && seen.insert(i.text.clone())
+            })
+            .collect();
+        added.reverse();
+
+        let count = added.len();
+        if let Some(path) = &options.histfile {
+            rewrite(path, |saved| {
+                saved.splice(0..0, added.iter().cloned());
+            })?;
+        }
+        added.append(&mut self.inputs);
+        self.inputs = added;
+        self.reset();
+        Ok(count)
+    }
+
+    fn keep(&self, line: &str, options: &Options) -> bool {
+        !(ignored(line, options)
+            || (options.histcontrol.ignoredups
+                && self.inputs.last().map(|i| i.text.as_str()) == Some(line)))
+    }
+
+    pub fn values(&self) -> HistoryIterator<'_> {
+        HistoryIterator {
+            iter: self.history.iter(),
+        }
+    }
+
+    /// The transcript with the metadata of each entry.
+    pub fn entries(&self) -> impl Iterator<Item = (&Source, &Meta)> {
+        self.history.iter().map(|(source, meta)| (source, meta))
+    }
+
+    pub fn inputs(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.inputs.iter().map(|i| i.text.as_str())
+    }
+
+    pub fn input_entries(&self) -> impl DoubleEndedIterator<Item = &Input> {
+        self.inputs.iter()
+    }
+
+    /// Go back to the scratch line without restoring it.
+    pub fn reset(&mut self) {
+        self.index = None;
+        self.prefix.clear();
+        self.scratch = None;
+    }
+
+    /// The input last recalled with `get_prev`/`get_next`, or the most
+    /// recent one when not navigating.
+    pub fn recalled(&self) -> Option<&str> {
+        self.index
+            .or_else(|| self.inputs.len().checked_sub(1))
+            .map(|i| self.inputs[i].text.as_str())
+    }
+
+    /// Recall the next older input starting with the text before the cursor
+    /// in `current`, like zsh's `history-beginning-search-backward`. Inputs
+    /// identical to `current` are skipped so runs of duplicates take one
+    /// keypress; with no prefix each step is constant-time.
+    pub fn get_prev(&mut self, current: &Cursor) -> Cursor {
+        let end = match self.index {
+            Some(i) => i,
+            None => {
+                self.prefix = current.before().to_string();
+                self.scratch = Some(current.clone());
+                self.inputs.len()
+            }
+        };
+
+        let shown = current.to_string();
+        let found = self.inputs[..end]
+            .iter()
+            .rposition(|i| i.text.starts_with(&self.prefix) && i.text != shown);
+        match found {
+            Some(i) => {
+                self.index = Some(i);
+                self.recall(i)
+            }
+            None if self.index.is_none() => {
+                self.reset();
+                current.clone()
+            }
+            None => current.clone(),
+        }
+    }
+
+    /// Recall the next newer matching input, or restore the scratch line
+    /// once past the newest one.
+    pub fn get_next(&mut self, current: &Cursor) -> Cursor {
+        let start = match self.index {
+            Some(i) => i + 1,
+            None => return current.clone(),
+        };
+
+        let shown = current.to_string();
+        let found = self.inputs[start..]
+            .iter()
+            .position(|i| i.text.starts_with(&self.prefix) && i.text != shown);
+        match found {
+            Some(n) => {
+                self.index = Some(start + n);
+                self.recall(start + n)
+            }
+            None => {
+                let scratch = self.scratch.take().unwrap_or_else(Cursor::new);
+                self.reset();
+                scratch
+            }
+        }
+    }
+
+    /// The input at `index` with the cursor just after the search prefix,
+    /// or at the end when there is none.
+    fn recall(&self, index: usize) -> Cursor {
+        let found = &self.inputs[index].text;
+        if self.prefix.is_empty() {
+            return Cursor::from(found.clone(), String::new());
+        }
+        let (before, after) = found.split_at(self.prefix.len());
+        Cursor::from(before.to_string(), after.to_string())
+    }
+
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+}
+
+pub struct HistoryIterator<'a> {
+    iter: std::slice::Iter<'a, (Source, Meta)>,
+}
+
+impl<'a> Iterator for HistoryIterator<'a> {
+    type Item = &'a Source;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(source, _)| source)
+    }
+}
+
+impl<'a> DoubleEndedIterator for HistoryIterator<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(source, _)| source)
+    }
+}
+
+/*
+impl IntoIterator for History {
+    type Item = Cursor;
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.history.into_iter()
+    }
+}
+*/
+
+/// Whether `line` should never be remembered: blank, starting with a space
+/// under `ignorespace`, or matching `histignore`.
+fn ignored(line: &str, options: &Options) -> bool {
+    line.trim().is_empty()
+        || (options.histcontrol.ignorespace && line.starts_with(' '))
+        || options.histignore.iter().any(|r| r.is_match(line))
+}
+
+/// Split a history file into entries, joining lines that end in a
+/// backslash with the next one as zsh does for multi-line inputs.
+pub fn continued(text: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut entry = String::new();
+    for line in text.lines() {
+        match line.strip_suffix('\\') {
+            Some(line) => {
+                entry.push_str(line);
+                entry.push('\n');
+            }
+            None => {
+                entry.push_str(line);
+                entries.push(std::mem::take(&mut entry));
+            }
+        }
+    }
+    if !entry.is_empty() {
+        entries.push(entry);
+    }
+    entries
+}
+
+/// Split a history file viri wrote into entries: an odd number of
+/// backslashes ends a line that goes on, and the rest are doubled ones.
+fn records(text: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut entry = String::new();
+    for line in text.lines() {
+        let trimmed = line.trim_end_matches('\\');
+        let slashes = line.len() - trimmed.len();
+        entry.push_str(trimmed);
+        entry.push_str(&"\\".repeat(slashes / 2));
+        if slashes % 2 == 1 {
+            entry.push('\n');
+        } else {
+            entries.push(std::mem::take(&mut entry));
+        }
+    }
+    if !entry.is_empty() {
+        entries.push(entry);
+    }
+    entries
+}
+
+/// Apply `edit` to everything saved in `path`, including what the current
+/// policies filtered out on load, and write the result back.
+fn rewrite(path: &Path, edit: impl FnOnce(&mut Vec<Input>)) -> Result<()> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut saved: Vec<Input> =
+        records(&text).iter().map(|l| Input::parse(l)).collect();
+    edit(&mut saved);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    let mut text = String::new();
+    for input in &saved {
+        text.push_str(&input.to_string());
+        text.push('\n');
+    }
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn append(path: &Path, line: &Input) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(history: &mut History, lines: &[&str], options: &Options) {
+        for line in lines {
+            history.add_input(line, options).unwrap();
+        }
+    }
+
+    fn inputs(history: &History) -> Vec<&str> {
+        history.inputs().collect()
+    }
+
+    fn line(text: &str) -> Cursor {
+        Cursor::from(text.to_string(), String::new())
+    }
+
+    #[test]
+    pub fn ignore_dups() {
+        let mut options = Options::new();
+        options.set("histcontrol=ignoredups").unwrap();
+        let mut history = History::new();
+        add(
+            &mut history,
+            &["select 1", "select 1", "select 2", "select 1"],
+            &options,
+        );
+        assert_eq!(inputs(&history), ["select 1", "select 2", "select 1"]);
+    }
+
+    #[test]
+    pub fn erase_dups() {
+        let mut options = Options::new();
+        options.set("histcontrol=erasedups").unwrap();
+        let mut history = History::new();
+        add(
+            &mut history,
+            &["select 1", "select 2", "select 1"],
+            &options,
+        );
+        assert_eq!(inputs(&history), ["select 2", "select 1"]);
+    }
+
+    #[test]
+    pub fn ignore_space_and_patterns() {
+        let mut options = Options::new();
+        options.set("histcontrol=ignorespace").unwrap();
+        options.set("histignore=(?i)password").unwrap();
+        options.set("histignore+=^\\\\q$").unwrap();
+        let mut history = History::new();
+        add(
+            &mut history,
+            &[
+                " secret",
+                "alter user set PASSWORD 'x'",
+                "\\q",
+                "select 1",
+                "",
+            ],
+            &options,
+        );
+        assert_eq!(inputs(&history), ["select 1"]);
+    }
+
+    #[test]
+    pub fn prefix_navigation() {
+        let mut history = History::new();
+        add(
+            &mut history,
+            &["select 1", "\\d users", "select 2", "\\dt"],
+            &Options::new(),
+        );
+
+        let typed = Cursor::from("sel".to_string(), "x".to_string());
+        let prev = history.get_prev(&typed);
+        assert_eq!(prev, Cursor::from("sel".to_string(), "ect 2".to_string()));
+        let prev = history.get_prev(&prev);
+        assert_eq!(prev.to_string(), "select 1");
+        assert_eq!(history.get_prev(&prev).to_string(), "select 1");
+
+        let next = history.get_next(&prev);
+        assert_eq!(next.to_string(), "select 2");
+        assert_eq!(history.get_next(&next), typed);
+    }
+
+    #[test]
+    pub fn navigate_empty() {
+        let mut history = History::new();
+        let typed = line("select");
+        assert_eq!(history.get_prev(&typed), typed);
+        assert_eq!(history.get_next(&typed), typed);
+        assert_eq!(history.recalled(), None);
+    }
+
+    #[test]
+    pub fn navigate_bounds() {
+        let mut history = History::new();
+        add(&mut history, &["a", "b", "c"], &Options::new());
+
+        let typed = Cursor::new();
+        let c = history.get_prev(&typed);
+        let b = history.get_prev(&c);
+        let a = history.get_prev(&b);
+        assert_eq!([&c, &b, &a].map(|l| l.to_string()), ["c", "b", "a"]);
+
+        // stays on the oldest input
+        assert_eq!(history.get_prev(&a), a);
+        assert_eq!(history.recalled(), Some("a"));
+
+        let b = history.get_next(&a);
+        let c = history.get_next(&b);
+        assert_eq!([&b, &c].map(|l| l.to_string()), ["b", "c"]);
+        assert_eq!(history.get_next(&c), typed);
+
+        // already at the bottom
+        assert_eq!(history.get_next(&typed), typed);
+        assert_eq!(history.recalled(), Some("c"));
+    }
+
+    #[test]
+    pub fn navigate_restores_scratch() {
+        let mut history = History::new();
+        add(&mut history, &["select 1"], &Options::new());
+
+        // nothing starts with the text before the cursor
+        let typed = line("update");
+        assert_eq!(history.get_prev(&typed), typed);
+        assert_eq!(history.get_next(&typed), typed);
+
+        let typed = Cursor::from(String::new(), "update users".to_string());
+        let mut recalled = history.get_prev(&typed);
+        assert_eq!(recalled.to_string(), "select 1");
+        recalled.insert('x');
+        assert_eq!(history.get_next(&recalled), typed);
+    }
+
+    #[test]
+    pub fn navigate_skips_duplicates() {
+        let mut history = History::new();
+        add(&mut history, &["a", "b", "b", "b", "c"], &Options::new());
+
+        let c = history.get_prev(&Cursor::new());
+        let b = history.get_prev(&c);
+        let a = history.get_prev(&b);
+        assert_eq!(a.to_string(), "a");
+        assert_eq!(history.get_next(&a).to_string(), "b");
+    }
+
+    #[test]
+    pub fn parse_history_lines() {
+        let plain = Input::parse("select 1");
+        assert_eq!(plain.text, "select 1");
+        assert_eq!(plain.meta, Meta::default());
+        assert_eq!(plain.to_string(), "select 1");
+
+        let zsh = Input::parse(": 1700000000:0;echo a;b");
+        assert_eq!(zsh.text, "echo a;b");
+        assert_eq!(
+            zsh.meta.time,
+            Some(UNIX_EPOCH + Duration::from_secs(1700000000))
+        );
+        assert_eq!(zsh.meta.session, None);
+        assert_eq!(zsh.to_string(), ": 1700000000:0;echo a;b");
+
+        let line = ": 1700000000:4242:/tmp/a%3Bb%25;select 1";
+        let viri = Input::parse(line);
+        assert_eq!(viri.text, "select 1");
+        assert_eq!(viri.meta.session, Some(4242));
+        assert_eq!(viri.meta.cwd, Some(PathBuf::from("/tmp/a;b%")));
+        assert_eq!(viri.to_string(), line);
+
+        assert_eq!(Input::parse(": not a time").text, ": not a time");
+    }
+
+    #[test]
+    pub fn cached_rows() {
+        let mut history = History::new();
+        history.set_width(4);
+        history.push(Source::ChildOutput(line("\x1b[1mabcde\x1b[0m")));
+        history.push(Source::ChildInput(line("ab")));
+        history.push(Source::ChildOutput(line("")));
+        assert_eq!(history.rows(), 4);
+        assert_eq!(history.rows_from(1), 2);
+        assert_eq!(history.rows_from(3), 0);
+        assert_eq!(history.entries_above(0), 3);
+        assert_eq!(history.entries_above(2), 1);
+        assert_eq!(history.entries_above(3), 1);
+        assert_eq!(history.entries_above(9), 0);
+        history.set_width(2);
+        assert_eq!(history.rows(), 5);
+    }
+
+    #[test]
+    pub fn trailing_backslashes() {
+        // a shell continuation, one ending a line of a multi-line input, a
+        // multi-line input and a path ending in two
+        let texts = [r"ls \", "a\\\nb", "select 1;\ngo", r"c:\\"];
+        let saved: String = texts
+            .iter()
+            .map(|t| {
+                let input = Input {
+                    text: t.to_string(),
+                    meta: Meta::default(),
+                };
+                format!("{}\n", input)
+            })
+            .collect();
+        let lines: Vec<&str> = saved.lines().collect();
+        assert_eq!(
+            lines,
+            [r"ls \\", r"a\\\", "b", r"select 1;\", "go", r"c:\\\\"]
+        );
+        assert_eq!(records(&saved), texts);
+    }
+
+    #[test]
+    pub fn persist_erase_dups() {
+        let path = std::env::temp_dir()
+            .join(format!("viri_history_test_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut options = Options::new();
+        options.set("histcontrol=erasedups").unwrap();
+        options.histfile = Some(path.clone());
+        let mut history = History::new();
+        add(&mut history, &["a", "b", "c", "a"], &options);
+        let saved = fs::read_to_string(&path).unwrap();
+        let saved: Vec<String> =
+            saved.lines().map(|l| Input::parse(l).text).collect();
+        assert_eq!(saved, ["b", "c", "a"]);
+
+        let mut loaded = History::new();
+        loaded.load(&path, &options).unwrap();
+        assert_eq!(inputs(&loaded), ["b", "c", "a"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn load_leaves_file() {
+        let path = std::env::temp_dir()
+            .join(format!("viri_history_load_{}", std::process::id()));
+        fs::write(&path, "a\n secret\na\nb\n").unwrap();
+
+        let mut options = Options::new();
+        options.set("histcontrol=ignorespace,erasedups").unwrap();
+        let mut loaded = History::new();
+        loaded.load(&path, &options).unwrap();
+        assert_eq!(inputs(&loaded), ["a", "b"]);
+        let saved = fs::read_to_string(&path).unwrap();
+        assert_eq!(saved, "a\n secret\na\nb\n");
+
+        // erasing a duplicate keeps what the policies filtered on load
+        options.histfile = Some(path.clone());
+        loaded.add_input("a", &options).unwrap();
+        assert_eq!(inputs(&loaded), ["b", "a"]);
+        let saved: Vec<String> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|l| Input::parse(l).text)
+            .collect();
+        assert_eq!(saved, [" secret", "b", "a"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn import_keeps_file() {
+        let path = std::env::temp_dir()
+            .join(format!("viri_history_import_{}", std::process::id()));
+        fs::write(&path, " secret\na\n").unwrap();
+
+        let mut options = Options::new();
+        options.set("histcontrol=ignorespace").unwrap();
+        let mut history = History::new();
+        history.load(&path, &options).unwrap();
+        options.histfile = Some(path.clone());
+        let imported = ["x", "a"].iter().map(|t| Input::parse(t)).collect();
+        assert_eq!(history.import(imported, &options).unwrap(), 1);
+        assert_eq!(inputs(&history), ["x", "a"]);
+        let saved = fs::read_to_string(&path).unwrap();
+        assert_eq!(saved, "x\n secret\na\n");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+use super::history::{continued, Input, Meta};
+use anyhow::{bail, Result};
+use std::{
+    fs,
+    path::Path,
+    str::FromStr,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// History file formats that can be imported.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Rlwrap,
+    Bash,
+    Zsh,
+    /// readline or libedit files, as written by python's `readline` module
+    Python,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "rlwrap" => Format::Rlwrap,
+            "bash" => Format::Bash,
+            "zsh" => Format::Zsh,
+            "python" | "readline" => Format::Python,
+            _ => bail!("unknown history format: {}", s),
+        })
+    }
+}
+
+/// Read every input in the history file at `path`, oldest first.
+pub fn read(path: &Path, format: Format) -> Result<Vec<Input>> {
+    let bytes = fs::read(path)?;
+    Ok(match format {
+        Format::Zsh => zsh(&bytes),
+        Format::Rlwrap | Format::Bash | Format::Python => {
+            readline(&String::from_utf8_lossy(&bytes))
+        }
+    })
+}
+
+/// One input per line. readline (and so rlwrap and bash) may precede a line
+/// with a `#{time}` comment, and libedit files start with `_HiStOrY_V2_`
+/// and escape characters as octal `\NNN`.
+fn readline(text: &str) -> Vec<Input> {
+    let mut lines = text.lines().peekable();
+    let libedit = lines.peek() == Some(&"_HiStOrY_V2_");
+    if libedit {
+        lines.next();
+    }
+
+    let mut inputs = Vec::new();
+    let mut time = None;
+    for line in lines {
+        if let Some(secs) = line.strip_prefix('#').and_then(|t| t.parse().ok())
+        {
+            time = Some(UNIX_EPOCH + Duration::from_secs(secs));
+            continue;
+        }
+        inputs.push(Input {
+            text: if libedit {
+                unescape_octal(line)
+            } else {
+                line.to_string()
+            },
+            meta: Meta {
+                time: time.take(),
+                ..Meta::default()
+            },
+        });
+    }
+    inputs
+}
+
+/// zsh's history, with or without `EXTENDED_HISTORY`. zsh "metafies" some
+/// bytes as `0x83` followed by the byte xor 32.
+fn zsh(bytes: &[u8]) -> Vec<Input> {
+    let mut unmeta = Vec::with_capacity(bytes.len());
+    let mut bytes = bytes.iter();
+    while let Some(&b) = bytes.next() {
+        match b {
+            0x83 => unmeta.extend(bytes.next().map(|b| b ^ 32)),
+            b => unmeta.push(b),
+        }
+    }
+
+    continued(&String::from_utf8_lossy(&unmeta))
+        .iter()
+        .map(|line| Input::parse(line))
+        .collect()
+}
+
+fn unescape_octal(line: &str) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        let code = rest
+            .get(i + 1..i + 4)
+            .and_then(|d| u8::from_str_radix(d, 8).ok());
+        match code {
+            Some(c) => {
+                out.push(c as char);
+                rest = &rest[i + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(inputs: &[Input]) -> Vec<&str> {
+        inputs.iter().map(|i| i.text.as_str()).collect()
+    }
+
+    #[test]
+    pub fn import_bash() {
+        let inputs = readline("select 1;\n#1700000000\n.tables\n");
+        assert_eq!(texts(&inputs), ["select 1;", ".tables"]);
+        assert_eq!(inputs[0].meta.time, None);
+        assert_eq!(
+            inputs[1].meta.time,
+            Some(UNIX_EPOCH + Duration::from_secs(1700000000))
+        );
+    }
+
+    #[test]
+    pub fn import_libedit() {
+        let inputs = readline("_HiStOrY_V2_\nprint(\\0401)\nx\\040=\\0402\n");
+        assert_eq!(texts(&inputs), ["print( 1)", "x = 2"]);
+    }
+
+    #[test]
+    pub fn import_zsh() {
+        let mut bytes =
+            b": 1700000000:0;for i in 1 2; do\\\n  echo $i\\\ndone\n".to_vec();
+        bytes.extend(b"echo \xc6\x83\xb2\n");
+        let inputs = zsh(&bytes);
+        assert_eq!(
+            texts(&inputs),
+            ["for i in 1 2; do\n  echo $i\ndone", "echo \u{192}"]
+        );
+        assert!(inputs[0].meta.time.is_some());
+    }
+}
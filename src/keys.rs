@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+/// Result of looking up a (possibly partial) key sequence in a `KeyMap`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Lookup {
+    /// nothing is bound to the sequence or to anything starting with it
+    Invalid,
+    /// the sequence is the start of at least one longer binding
+    Prefix,
+    /// the sequence is bound and is not the start of any other binding
+    Complete(String),
+    /// the sequence is bound but is also the start of a longer binding
+    Ambiguous(String),
+}
+
+/// Trie of key sequences to the name of the function they run.
+#[derive(Clone, Debug, Default)]
+pub struct KeyMap {
+    action: Option<String>,
+    next: HashMap<char, KeyMap>,
+}
+
+impl KeyMap {
+    pub fn new() -> Self {
+        KeyMap::default()
+    }
+
+    pub fn insert(&mut self, keys: &str, action: String) {
+        let node = keys
+            .chars()
+            .fold(self, |node, c| node.next.entry(c).or_default());
+        node.action = Some(action);
+    }
+
+    pub fn lookup(&self, keys: &str) -> Lookup {
+        let mut node = self;
+        for c in keys.chars() {
+            match node.next.get(&c) {
+                Some(n) => node = n,
+                None => return Lookup::Invalid,
+            }
+        }
+
+        match (&node.action, node.next.is_empty()) {
+            (Some(action), true) => Lookup::Complete(action.clone()),
+            (Some(action), false) => Lookup::Ambiguous(action.clone()),
+            (None, false) => Lookup::Prefix,
+            (None, true) => Lookup::Invalid,
+        }
+    }
+
+    /// The longest bound sequence `keys` starts with, as how many keys it
+    /// takes and its action.
+    pub fn longest(&self, keys: &str) -> Option<(usize, String)> {
+        let mut node = self;
+        let mut found = None;
+        for (i, c) in keys.chars().enumerate() {
+            match node.next.get(&c) {
+                Some(n) => node = n,
+                None => break,
+            }
+            if let Some(action) = &node.action {
+                found = Some((i + 1, action.clone()));
+            }
+        }
+        found
+    }
+
+    /// Every bound sequence with its action, sorted by sequence.
+    pub fn bindings(&self) -> Vec<(String, String)> {
+        let mut found = Vec::new();
+        self.collect(&mut String::new(), &mut found);
+        found.sort();
+        found
+    }
+
+    fn collect(&self, keys: &mut String, found: &mut Vec<(String, String)>) {
+        if let Some(action) = &self.action {
+            found.push((keys.clone(), action.clone()));
+        }
+        for (c, node) in &self.next {
+            keys.push(*c);
+            node.collect(keys, found);
+            keys.pop();
+        }
+    }
+}
+
+impl From<HashMap<String, String>> for KeyMap {
+    fn from(bindings: HashMap<String, String>) -> Self {
+        let mut keys = KeyMap::new();
+        for (k, action) in bindings {
+            keys.insert(&k, action);
+        }
+        keys
+    }
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> KeyMap {
+        let mut keys = KeyMap::new();
+        keys.insert("d", "Delete".to_string());
+        keys.insert("dd", "DeleteLine".to_string());
+        keys.insert("gg", "Top".to_string());
+        keys
+    }
+
+    #[test]
+    pub fn lookup_complete() {
+        assert_eq!(
+            keys().lookup("dd"),
+            Lookup::Complete("DeleteLine".to_string())
+        );
+        assert_eq!(keys().lookup("gg"), Lookup::Complete("Top".to_string()));
+    }
+
+    #[test]
+    pub fn lookup_prefix() {
+        assert_eq!(keys().lookup("g"), Lookup::Prefix);
+    }
+
+    #[test]
+    pub fn lookup_ambiguous() {
+        assert_eq!(keys().lookup("d"), Lookup::Ambiguous("Delete".to_string()));
+    }
+
+    #[test]
+    pub fn lookup_invalid() {
+        assert_eq!(keys().lookup("x"), Lookup::Invalid);
+        assert_eq!(keys().lookup("gx"), Lookup::Invalid);
+        assert_eq!(keys().lookup("ddd"), Lookup::Invalid);
+    }
+
+    #[test]
+    pub fn longest_bound() {
+        let mut keys = keys();
+        keys.insert("dab", "DeleteAll".to_string());
+        assert_eq!(keys.longest("da"), Some((1, "Delete".to_string())));
+        assert_eq!(keys.longest("ddx"), Some((2, "DeleteLine".to_string())));
+        assert_eq!(keys.longest("g"), None);
+    }
+}
@@ -0,0 +1,157 @@
+use super::{
+    ansi::{self, Cell, Style, TAB},
+    cursor::Cursor,
+    history::Source,
+};
+
+/// The row, column and width of each character of a line, then the row and
+/// column just after the last one.
+type Places = (Vec<(usize, usize, usize)>, (usize, usize));
+
+/// Where the characters of a line go once wrapped at `width` columns. Wide
+/// characters go to the next row rather than be split, and tabs reach the
+/// next tab stop as in `ansi::parse`.
+fn place(line: impl Iterator<Item = char>, width: usize) -> Places {
+    let width = width.max(1);
+    let (mut row, mut col, mut shown) = (0, 0, 0);
+    let mut places = Vec::new();
+    for c in line {
+        let cols = match c {
+            '\t' => (TAB - shown % TAB).min(width),
+            c => ansi::cols(c),
+        };
+        if col > 0 && col + cols > width {
+            row += 1;
+            col = 0;
+        }
+        places.push((row, col, cols));
+        col += cols;
+        shown += cols;
+    }
+    match col >= width {
+        true => (places, (row + 1, 0)),
+        false => (places, (row, col)),
+    }
+}
+
+/// Screen rows a line of `chars` takes; at least one.
+fn line_height(chars: impl Iterator<Item = char>, width: usize) -> usize {
+    place(chars, width)
+        .0
+        .last()
+        .map_or(1, |&(row, _, _)| row + 1)
+}
+
+/// Screen rows `text` takes once wrapped at `width` columns, each of its
+/// lines starting a new row; at least one.
+pub fn height(text: &str, width: usize) -> usize {
+    text.split('\n')
+        .map(|line| line_height(line.chars(), width))
+        .sum()
+}
+
+/// Split `cells` into rows at most `width` columns wide, breaking at
+/// newlines too, with tabs turned into the spaces they take.
+pub fn rows(cells: &[Cell], width: usize) -> Vec<Vec<Cell>> {
+    cells
+        .split(|&(c, _)| c == '\n')
+        .flat_map(|line| {
+            let (places, _) = place(line.iter().map(|&(c, _)| c), width);
+            let mut rows = vec![Vec::new()];
+            for (&(c, style), &(row, _, cols)) in line.iter().zip(&places) {
+                if row == rows.len() {
+                    rows.push(Vec::new());
+                }
+                match c {
+                    '\t' => rows[row].extend(vec![(' ', style); cols]),
+                    c => rows[row].push((c, style)),
+                }
+            }
+            rows
+        })
+        .collect()
+}
+
+/// Split `text` into rows at most `width` columns wide, breaking at its
+/// newlines too.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let cells: Vec<Cell> =
+        text.chars().map(|c| (c, Style::default())).collect();
+    rows(&cells, width)
+        .iter()
+        .map(|row| row.iter().map(|&(c, _)| c).collect())
+        .collect()
+}
+
+/// Row and column, from 0, of a cursor after `before`. A cursor after a
+/// full row is at the start of the next.
+pub fn position(before: &str, width: usize) -> (usize, usize) {
+    let mut lines = before.split('\n');
+    let last = lines.next_back().unwrap_or_default();
+    let above: usize = lines.map(|line| height(line, width)).sum();
+    let (_, (row, col)) = place(last.chars(), width);
+    (above + row, col)
+}
+
+/// Rows the input line takes, counting the row the cursor wraps onto.
+pub fn input_height(input: &Cursor, width: usize) -> usize {
+    let (row, _) = position(input.before(), width);
+    height(&input.to_string(), width).max(row + 1)
+}
+
+/// Rows a transcript entry takes, leaving out its escapes.
+pub fn entry_height(source: &Source, width: usize) -> usize {
+    match source {
+        Source::ChildInput(c)
+        | Source::ChildOutput(c)
+        | Source::ChildError(c) => {
+            let cells = ansi::parse(&c.to_string());
+            line_height(cells.iter().map(|&(c, _)| c), width)
+        }
+    }
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn wrap_rows() {
+        assert_eq!(height("", 4), 1);
+        assert_eq!(height("abcd", 4), 1);
+        assert_eq!(height("abcde", 4), 2);
+        assert_eq!(wrap("abcdéfghi", 4), ["abcd", "éfgh", "i"]);
+        assert_eq!(wrap("", 4), [""]);
+        assert_eq!(height("ab\n\nabcde", 4), 4);
+        assert_eq!(wrap("ab\n\nabcde", 4), ["ab", "", "abcd", "e"]);
+    }
+
+    #[test]
+    pub fn wide_and_tabs() {
+        // a wide character that would not fit goes to the next row
+        assert_eq!(wrap("abc日本", 4), ["abc", "日本"]);
+        assert_eq!(height("abc日本", 4), 2);
+        assert_eq!(position("abc日", 4), (1, 2));
+        assert_eq!(position("日本", 4), (1, 0));
+        assert_eq!(wrap("a\tb", 12), ["a       b"]);
+        assert_eq!(position("a\t", 12), (0, 8));
+        assert_eq!(height("a\tbcd", 8), 2);
+        assert_eq!(wrap("日\tb", 80), ["日      b"]);
+    }
+
+    #[test]
+    pub fn cursor_position() {
+        assert_eq!(position("abc", 4), (0, 3));
+        assert_eq!(position("abcd", 4), (1, 0));
+        assert_eq!(position("abcde\nab", 4), (2, 2));
+        assert_eq!(position("ab\n", 4), (1, 0));
+        let input = Cursor::from("abcd".to_string(), String::new());
+        assert_eq!(input_height(&input, 4), 2);
+        let input = Cursor::from("ab".to_string(), "cd".to_string());
+        assert_eq!(input_height(&input, 4), 1);
+    }
+}
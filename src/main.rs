@@ -1,38 +1,78 @@
+mod ansi;
 mod child;
+mod command;
 mod config;
 mod cursor;
+mod expand;
+mod export;
+mod highlight;
+mod history;
+mod import;
+mod keys;
+mod layout;
 mod output;
+mod replay;
+mod search;
 mod state;
+mod statusline;
+mod substitute;
+mod syntax;
 
 use anyhow::Result;
-use config::get_config;
+use config::{get_config, histfile, rc_path};
 use cursor::Cursor;
+use keys::KeyMap;
 use mio::{unix::SourceFd, Events, Interest, Poll, Token};
-// use output::{print_buffer, OutputType};
+use output::Output;
+use history::Source;
 use state::{
-    command_mode, insert_mode, normal_mode, History, Mode, Source, State,
+    check_timeout, command_mode, insert_mode, mouse, normal_mode, replace_mode,
+    Mode, State,
 };
 use std::{
     env, error,
-    io::{stdin, stdout, Write},
-    os::unix::prelude::AsRawFd,
+    fs::{self, OpenOptions},
+    io::{self, stdin, stdout, Write},
+    os::unix::{fs::OpenOptionsExt, prelude::AsRawFd},
+    path::{Path, PathBuf},
+    process,
     sync::mpsc::channel,
     time::Duration,
 };
 use termion::{
-    clear,
+    event::Event,
     // event::{Event, Key},
     input::TermRead,
-    raw::IntoRawMode,
+    raw::{IntoRawMode, RawTerminal},
     terminal_size,
 };
-use tokio::{runtime::Runtime, sync::mpsc::unbounded_channel};
+use tokio::{
+    runtime::Runtime,
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+};
+
+/// most lines of child output taken into one frame
+const OUTPUT_BURST: usize = 4096;
+
+const USAGE: &str = "usage: viri {program} [args...]
+       viri import {rlwrap|bash|zsh|python} {file} {program}
+       viri export {json|text|script} {file|-} {program}";
 
 fn main() -> Result<(), Box<dyn error::Error + 'static>> {
     let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None => return Err(USAGE.into()),
+        Some("import") => return import_history(&args[1..]),
+        Some("export") => return export_history(&args[1..]),
+        Some(_) => {}
+    }
+
+    let program = Path::new(&args[0])
+        .file_name()
+        .map_or_else(|| args[0].clone(), |n| n.to_string_lossy().into_owned());
     let term_in = stdin();
     let mut term_events = stdin().events();
-    let mut output = stdout().into_raw_mode()?;
+    let mut output = Output::new(stdout().into_raw_mode()?);
 
     let (child_send, child_recv) = unbounded_channel();
     let (parent_send, parent_recv) = channel();
@@ -62,18 +102,8 @@ fn main() -> Result<(), Box<dyn error::Error + 'static>> {
     //     Ok(())
     // });
 
-    let size = terminal_size()?;
-    let mut state: State = State {
-        mode: Mode::Normal,
-        size,
-        input: Cursor::new(),
-        command: Cursor::new(),
-        keys: get_config("_filename")?,
-        history: History::new(),
-    };
-
-    write!(output, "{}", clear::All)?;
-    output.flush()?;
+    let mut state = new_state(program, terminal_size()?)?;
+    output.draw(&state)?;
 
     let mut poll = Poll::new()?;
     let mut events = Events::with_capacity(128);
@@ -91,68 +121,84 @@ fn main() -> Result<(), Box<dyn error::Error + 'static>> {
             break 'main;
         }
 
-        if let Ok(cout) = parent_recv.try_recv() {
-            state
-                .history
-                .push(Source::ChildOutput(Cursor::from(cout, String::new())));
-            output::print_buffer(&mut output, &state)?;
+        // a burst of output from the child is drawn as one frame
+        let mut dirty = false;
+        for update in parent_recv.try_iter().take(OUTPUT_BURST) {
+            match update {
+                child::Update::Started(pid) => state.child.pid = Some(pid),
+                child::Update::Exited(exit) => state.child.exit = Some(exit),
+                child::Update::Output(line) => {
+                    state = child_output(state, line, Source::ChildOutput)
+                }
+                child::Update::Error(line) => {
+                    state = child_output(state, line, Source::ChildError)
+                }
+            }
+            dirty = true;
         }
 
-        poll.poll(&mut events, Some(Duration::from_nanos(30)))?;
+        poll.poll(&mut events, Some(Duration::from_millis(10)))?;
+
+        let size = terminal_size()?;
+        if size != state.size {
+            state.size = size;
+            state.history.set_width(size.0 as usize);
+            output.invalidate();
+            dirty = true;
+        }
+
+        if state.pending.is_some() {
+            state = check_timeout(state);
+            // the mapping that timed out may have sent or opened the input
+            if state.pending.is_none() {
+                state = settle(state, &mut output, &child_send)?;
+            }
+            dirty = true;
+        }
+
+        if state.replay.is_some() {
+            let (next, changed) = replay::tick(state);
+            state = match next.mode {
+                Mode::Execute => execute(next, &child_send)?,
+                _ => next,
+            };
+            dirty |= changed;
+        }
 
         for event in events.iter() {
             match event.token() {
                 TERM_EVENT => {
                     if let Some(term_event) = term_events.next() {
                         let term_event = term_event?;
-                        state = match state.mode {
-                            Mode::Execute => {
-                                let cin = format!("{}\n", state.input);
-                                child_send
-                                    .send(child::Message::Exec(cin.clone()))?;
-
-                                state.history.push(Source::ChildInput(
-                                    state.input.to_owned(),
-                                ));
-                                state.mode = Mode::Normal;
-                                state.input = Cursor::new();
-                                state
+                        state.status = None;
+                        state = match (term_event, &state.mode) {
+                            (Event::Mouse(evt), _) => mouse(evt, state),
+                            (_, Mode::Execute) => execute(state, &child_send)?,
+                            (_, Mode::Edit) => edit(state, &mut output)?,
+                            (evt, Mode::Command)
+                            | (evt, Mode::Search { .. }) => {
+                                command_mode(evt, state)
                             }
-
-                            Mode::Command => command_mode(term_event, state),
-                            Mode::Insert => insert_mode(term_event, state),
-                            Mode::Normal => normal_mode(term_event, state),
-                            Mode::Quit => break 'main,
+                            (evt, Mode::Insert) => insert_mode(evt, state),
+                            (evt, Mode::Replace) => replace_mode(evt, state),
+                            (evt, Mode::Normal) => normal_mode(evt, state),
+                            (_, Mode::Quit) => break 'main,
                         };
 
-                        output::print_buffer(&mut output, &state)?;
-
-                        state = match state.mode {
-                            Mode::Execute => {
-                                let cin = format!("{}\n", state.input);
-                                child_send
-                                    .send(child::Message::Exec(cin.clone()))?;
-
-                                state.history.push(Source::ChildInput(
-                                    state.input.to_owned(),
-                                ));
-                                state.mode = Mode::Normal;
-                                state.input = Cursor::new();
-                                state
-                            }
-                            Mode::Quit => break 'main,
-                            _ => {
-                                child_send.send(child::Message::Input(
-                                    state.input.clone(),
-                                ))?;
-                                state
-                            }
-                        };
+                        state = settle(state, &mut output, &child_send)?;
+                        if let Mode::Quit = state.mode {
+                            break 'main;
+                        }
+                        dirty = true;
                     }
                 }
                 _ => break 'main,
             }
         }
+
+        if dirty {
+            output.draw(&state)?;
+        }
     }
 
     // loop {
@@ -206,9 +252,281 @@ fn main() -> Result<(), Box<dyn error::Error + 'static>> {
 
     child_send.send(child::Message::Kill)?;
     output_t.join().unwrap()?;
+    output.finish()?;
     drop(output);
 
     println!("\nfinished\n\n",);
 
     Ok(())
 }
+
+/// Set up the editor state for wrapping `program`: run the rc file, then
+/// load the history file it settled on.
+fn new_state(program: String, size: (u16, u16)) -> Result<State> {
+    let mut state =
+        State::new(program, size, KeyMap::from(get_config("_filename")?));
+    state.options.histfile = histfile(&state.program);
+
+    if let Some(rc) = rc_path().filter(|rc| rc.exists()) {
+        state = command::source(&rc.to_string_lossy(), state);
+    }
+
+    if let Some(path) = state.options.histfile.clone() {
+        if let Err(e) = state.history.load(&path, &state.options) {
+            state.status = Some(format!("E: {}: {}", path.display(), e));
+        }
+    }
+    Ok(state)
+}
+
+/// `viri import {format} {file} {program}`: add another history file to
+/// the one viri keeps for `program`.
+fn import_history(args: &[String]) -> Result<(), Box<dyn error::Error>> {
+    let (format, file, program) = match args {
+        [format, file, program] => (format.parse()?, file, program),
+        _ => return Err(USAGE.into()),
+    };
+    let mut state = new_state(program.clone(), (80, 24))?;
+    if let Some(status) = state.status.take() {
+        return Err(status.into());
+    }
+
+    let imported = import::read(Path::new(file), format)?;
+    let count = state.history.import(imported, &state.options)?;
+    match &state.options.histfile {
+        Some(path) => {
+            println!("imported {} inputs into {}", count, path.display())
+        }
+        None => println!("imported {} inputs, but histfile is not set", count),
+    }
+    Ok(())
+}
+
+/// `viri export {format} {file} {program}`: write out the inputs viri keeps
+/// for `program`, to stdout if `file` is `-`.
+fn export_history(args: &[String]) -> Result<(), Box<dyn error::Error>> {
+    let (format, file, program) = match args {
+        [format, file, program] => (format.parse()?, file, program),
+        _ => return Err(USAGE.into()),
+    };
+    let state = new_state(program.clone(), (80, 24))?;
+    if let Some(status) = state.status {
+        return Err(status.into());
+    }
+
+    let inputs = state
+        .history
+        .input_entries()
+        .map(|i| (export::Kind::Input, i.text.clone(), &i.meta));
+    let text = export::render(inputs, format);
+    match file.as_str() {
+        "-" => print!("{}", text),
+        file => std::fs::write(file, text)?,
+    }
+    Ok(())
+}
+
+/// Add a line the child wrote to the transcript, as a `source`.
+fn child_output(
+    mut state: State,
+    line: String,
+    source: fn(Cursor) -> Source,
+) -> State {
+    if let Some(replay) = state.replay.as_mut() {
+        replay.saw_output(&line, &state.options);
+    }
+    state
+        .history
+        .push(source(Cursor::from(line, String::new())));
+    state.scroll = 0;
+    state
+}
+
+/// Act on the mode a key, or a mapping that timed out, left: edit or send
+/// the input, or else pass the input line on to the child.
+fn settle<W: Write>(
+    mut state: State,
+    output: &mut Output<RawTerminal<W>>,
+    child_send: &UnboundedSender<child::Message>,
+) -> Result<State> {
+    if let Mode::Edit = state.mode {
+        state = edit(state, output)?;
+    }
+    match state.mode {
+        Mode::Execute => execute(state, child_send),
+        Mode::Quit => Ok(state),
+        _ => {
+            child_send.send(child::Message::Input(state.input.clone()))?;
+            Ok(state)
+        }
+    }
+}
+
+/// Open the input in `$VISUAL` or `$EDITOR`, out of raw mode, then load
+/// back what was saved, or with `editexec` send it.
+fn edit<W: Write>(
+    mut state: State,
+    output: &mut Output<RawTerminal<W>>,
+) -> Result<State> {
+    state.mode = Mode::Normal;
+    let lexer = syntax::lexer(&state.options.syntax).unwrap_or(&syntax::NONE);
+    let text = format!("{}\n", state.input);
+    let path = match temp_file(lexer.extension, &text) {
+        Ok(path) => path,
+        Err(e) => {
+            state.status = Some(format!("E: {}", e));
+            return Ok(state);
+        }
+    };
+
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    output.suspend()?;
+    let status = process::Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status();
+    output.resume()?;
+
+    // on any failure the input is kept as it was
+    let edited = match status {
+        Ok(status) if status.success() => fs::read_to_string(&path)
+            .map_err(|e| format!("E: {}: {}", path.display(), e)),
+        Ok(status) => Err(format!("E: {} exited with {}", program, status)),
+        Err(e) => Err(format!("E: {}: {}", program, e)),
+    };
+    let _ = fs::remove_file(&path);
+    match edited {
+        Ok(text) => {
+            let text = text.strip_suffix('\n').unwrap_or(&text);
+            state.input = Cursor::from(text.to_string(), String::new());
+            if state.options.editexec {
+                state.mode = Mode::Execute;
+            }
+        }
+        Err(e) => state.status = Some(e),
+    }
+    Ok(state)
+}
+
+/// Write `text` to a new file in the temporary directory that only the user
+/// can read, never opening one someone else left there, even as a link.
+fn temp_file(extension: &str, text: &str) -> io::Result<PathBuf> {
+    for n in 0..100 {
+        let file = format!("viri-{}-{}.{}", process::id(), n, extension);
+        let path = env::temp_dir().join(file);
+        let created = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path);
+        match created {
+            Ok(mut file) => {
+                file.write_all(text.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "no free temporary file name",
+    ))
+}
+
+/// Send the input line to the child and record it in the history.
+fn execute(
+    mut state: State,
+    child_send: &UnboundedSender<child::Message>,
+) -> Result<State> {
+    let line = state.input.to_string();
+    state.mode = Mode::Normal;
+    state.input = Cursor::new();
+    state.scroll = 0;
+
+    // bash's ^old^new quick substitution and !-designators, which some
+    // programs' own syntax needs turned off with `set nohistexpand`
+    let expanded = if state.options.histexpand {
+        let prev = state.history.inputs().next_back().unwrap_or_default();
+        match substitute::quick(&line, prev) {
+            Some(quick) => quick.map(Some),
+            None => expand::expand(&line, &state.history),
+        }
+    } else {
+        Ok(None)
+    };
+    let line = match expanded {
+        Ok(expanded) => expanded.unwrap_or(line),
+        Err(e) => {
+            state.status = Some(format!("E: {}", e));
+            state.input = Cursor::from(line, String::new());
+            return Ok(state);
+        }
+    };
+
+    child_send.send(child::Message::Exec(format!("{}\n", line)))?;
+    if let Some(replay) = state.replay.as_mut() {
+        replay.sent();
+    }
+    if let Err(e) = state.history.add_input(&line, &state.options) {
+        state.status = Some(format!("E: history: {}", e));
+    }
+    // each line of a multi-line input is echoed as its own entry
+    for line in line.split('\n') {
+        state.history.push(Source::ChildInput(Cursor::from(
+            line.to_string(),
+            String::new(),
+        )));
+    }
+    Ok(state)
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    fn named(n: usize, extension: &str) -> PathBuf {
+        let file = format!("viri-{}-{}.{}", process::id(), n, extension);
+        env::temp_dir().join(file)
+    }
+
+    #[test]
+    pub fn temp_file_private() {
+        let path = temp_file("tmptest", "select 1;").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "select 1;");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn temp_file_skips_taken_names() {
+        // a file and a link someone left under the first names
+        let taken = named(0, "tmptaken");
+        fs::write(&taken, "theirs").unwrap();
+        let target = env::temp_dir()
+            .join(format!("viri-{}-target", process::id()));
+        fs::write(&target, "theirs").unwrap();
+        let link = named(1, "tmptaken");
+        let _ = fs::remove_file(&link);
+        symlink(&target, &link).unwrap();
+
+        let path = temp_file("tmptaken", "mine").unwrap();
+        assert_eq!(path, named(2, "tmptaken"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "mine");
+        assert_eq!(fs::read_to_string(&taken).unwrap(), "theirs");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "theirs");
+        for path in [path, taken, link, target] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -1,211 +1,546 @@
+use super::{
+    ansi::{self, Cell, Style},
+    config::{Display, Shape},
+    highlight,
+    history::Source,
+    layout,
+    state::{Mode, State},
+    statusline, syntax,
+};
+use std::io::{self, Write};
+use termion::{clear, cursor as tcursor, raw::RawTerminal, screen};
 
-use super::{cursor::Cursor, state::State, state::Source};
-use anyhow::Result;
-use termion::{clear, raw::RawTerminal, cursor as tcursor};
-use std::io::Write;
-use tokio::sync::mpsc::Receiver;
-
-#[derive(Debug)]
-pub enum OutputType {
-    Child(String),
-    Input(Cursor),
-    Exec(String),
+/// What the screen should show: each row's text, styles included, and where
+/// the cursor goes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Frame {
+    rows: Vec<String>,
+    /// column and row, from 1
+    cursor: (u16, u16),
 }
 
-#[derive(Debug)]
-pub struct Output {
-    recv: Receiver<OutputType>,
-    input: Cursor,
-    output: Vec<String>,
+/// Turns on mouse reporting, as termion's `MouseTerminal` does.
+const MOUSE_ON: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+const MOUSE_OFF: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+
+/// Draws frames on the terminal, rewriting only the rows that changed since
+/// the last one.
+pub struct Output<W: Write> {
+    out: W,
+    /// the display the last frame was drawn for
+    display: Option<Display>,
+    last: Option<Frame>,
+    /// transcript entries already written to the scrollback, when inline
+    printed: usize,
+    shape: Shape,
 }
 
-impl Output {
-    pub fn new(recv: Receiver<OutputType>) -> Self {
-        Output { recv, output: Vec::new(), input: Cursor::new() }
-    }
-
-    pub async fn run_output(mut self) -> Result<()> {
-        while let Some(m) = self.recv.recv().await {
-            // self.print_buffer(m).await?;
-            match m {
-                OutputType::Child(s) => {
-                    self.output.push(s.clone());
-                }
-
-                OutputType::Input(c) => {
-                    self.input = c;
-                    // self.print_input(&c).await?;
-                    // self.input = c.clone();
-                    // let mut buf = format!("{}{}", cursor::Goto(1, state.size.1), clear::All);
-                    // buf.push_str(&format!("{}{:?}", cursor::Goto(1, state.size.1 - 1), state.mode));
-                    // buf.push_str(&format!("{}", cursor::Goto(state.input.pos() as u16, state.size.1)));
-                    // println!("{}", buf);
-                }
-
-                OutputType::Exec(s) => {
-                    self.input = Cursor::new();
-                    self.output.push(s.trim_end().to_string());
-                }
+impl<W: Write> Output<W> {
+    pub fn new(out: W) -> Self {
+        Output {
+            out,
+            display: None,
+            last: None,
+            printed: 0,
+            shape: Shape::Default,
+        }
+    }
+
+    pub fn draw(&mut self, state: &State) -> io::Result<()> {
+        let display = state.options.display;
+        let mut buf = String::new();
+        if self.display != Some(display) {
+            buf.push_str(&self.leave(false));
+            if display == Display::Inline {
+                self.printed = state.history.len();
             }
-        self.print_buffer().await?;
+            buf.push_str(&enter(display));
+            self.display = Some(display);
+            self.last = None;
+        }
 
+        match display {
+            Display::Inline => buf.push_str(&self.inline(state)),
+            Display::Fullscreen | Display::Altscreen => {
+                let frame = frame(state);
+                buf.push_str(&diff(self.last.as_ref(), &frame));
+                self.last = Some(frame);
+            }
         }
-        Ok(())
+
+        let shapes = &state.options.cursorshape;
+        let shape = match state.mode {
+            Mode::Normal => shapes.normal,
+            Mode::Insert => shapes.insert,
+            Mode::Replace => shapes.replace,
+            Mode::Command | Mode::Search { .. } => shapes.command,
+            Mode::Execute | Mode::Edit | Mode::Quit => self.shape,
+        };
+        if shape != self.shape {
+            buf.push_str(&shape.escape());
+            self.shape = shape;
+        }
+        self.write(&buf)
     }
 
-    /*
-    async fn print_input(&self, input: &Cursor) -> Result<(), std::io::Error> {
-        let mut buf = String::new();
-        buf.push_str(&input.to_string());
-        buf.push_str(&tcursor::Goto(input.pos() as u16, 25).to_string());
-        println!("{}", buf);
+    /// Draw the next frame whole, as when the terminal was resized.
+    pub fn invalidate(&mut self) {
+        self.last = None;
+    }
+
+    /// Give the terminal back to the shell, with its own cursor shape. This
+    /// is done on drop as well, should viri stop on an error or a panic.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.release()?;
+        self.display = None;
         Ok(())
     }
-    */
-
-    async fn print_buffer(
-        &self,
-    ) -> Result<(), std::io::Error> {
-        let mut buf = format!("{}{}", clear::All, tcursor::Goto(1, 1));
-
-        self.output
-            .iter()
-            .skip(if self.output.len() > 24 { self.output.len() - 24 } else { 0 })
-            .for_each(|o: &String| {
-                buf.push_str(o);
-                buf.push('\n');
-                buf.push('\r');
-            });
-
-        buf.push_str(
-            &tcursor::Goto(
-                1,
-                if self.output.len() > 24 {
-                    25
-                } else {
-                    self.output.len() as u16  + 1
-                }
-            ).to_string()
-        );
-        buf.push_str(&self.input.to_string());
-        buf.push_str(
-            &tcursor::Goto(
-                self.input.pos() as u16,
-                if self.output.len() > 24 {
-                    25
-                } else {
-                    self.output.len() as u16
-                }
-            ).to_string()
-        );
-        println!("{}", buf);
-
-        // println!("{}\n\r", buf);
-
-        // output.write_all(&mut buf.as_bytes()).await?;
-
-        /*
-           write!(output, "{clear_all}", clear_all = clear::All,)?;
-           for (i, o) in state.output.iter().enumerate() {
-           write!(
-           output,
-           "{output_pos}",
-           output_pos = cursor::Goto(1, 1 + i as u16)
-           )?;
-           write!(output, "{}", o).unwrap();
-           }
-           write!(
-           output,
-           "{state_pos}: {state_mode:?}{command_pos}command: {command}{before_pos}{before}{after}{cursor_pos}",
-           state_pos = cursor::Goto(1, state.size.1 - 2),
-           state_mode = state.mode,
-           command_pos = cursor::Goto(1, state.size.1 - 1),
-           command = format!("{}{}", state.cmd_before, state.cmd_after),
-           before_pos = cursor::Goto(1, state.size.1),
-           before = state.input_before,
-           after = state.input_after.chars().rev().collect::<String>(),
-           cursor_pos = cursor::Goto(state.input_before.len() as u16 + 1, state.size.1),
-           )?;
-           output.flush()?;
-           */
+
+    fn release(&mut self) -> io::Result<()> {
+        let mut buf = self.leave(true);
+        if self.shape != Shape::Default {
+            buf.push_str(&Shape::Default.escape());
+            self.shape = Shape::Default;
+        }
+        self.write(&buf)
+    }
+
+    fn write(&mut self, buf: &str) -> io::Result<()> {
+        if !buf.is_empty() {
+            self.out.write_all(buf.as_bytes())?;
+            self.out.flush()?;
+        }
         Ok(())
     }
+
+    /// Undo the current display, leaving a full screen as it is when
+    /// `exiting`.
+    fn leave(&self, exiting: bool) -> String {
+        match (self.display, &self.last) {
+            (None, _) => String::new(),
+            (Some(Display::Inline), _) => self.clear_region(),
+            (Some(Display::Fullscreen), Some(last)) if exiting => format!(
+                "{}{}",
+                MOUSE_OFF,
+                tcursor::Goto(1, last.rows.len() as u16)
+            ),
+            (Some(Display::Fullscreen), _) => {
+                format!("{}{}{}", MOUSE_OFF, clear::All, tcursor::Goto(1, 1))
+            }
+            (Some(Display::Altscreen), _) => {
+                format!("{}{}", MOUSE_OFF, screen::ToMainScreen)
+            }
+        }
+    }
+
+    /// Write new transcript entries into the scrollback and redraw the
+    /// input and status rows below them.
+    fn inline(&mut self, state: &State) -> String {
+        let width = state.size.0.max(1) as usize;
+        let height = state.size.1.max(1) as usize;
+        let (status, command_cursor) = status_rows(state, width, height);
+        let (mut rows, (row, col)) =
+            input_rows(state, width, height - status.len());
+        let (col, row) = match command_cursor {
+            Some((r, c)) => (c, rows.len() + r),
+            None => (col, row),
+        };
+        rows.extend(status);
+        let frame = Frame {
+            rows,
+            cursor: (col as u16 + 1, row as u16 + 1),
+        };
+
+        let len = state.history.len();
+        if len == self.printed && self.last.as_ref() == Some(&frame) {
+            return String::new();
+        }
+
+        let mut buf = self.clear_region();
+        for source in state.history.values().skip(self.printed) {
+            buf.push_str(&ansi::render(&cells(source, state)));
+            buf.push_str("\r\n");
+        }
+        self.printed = len;
+
+        buf.push_str(&frame.rows.join("\r\n"));
+        let up = frame.rows.len() - frame.cursor.1 as usize;
+        if up > 0 {
+            buf.push_str(&tcursor::Up(up as u16).to_string());
+        }
+        buf.push('\r');
+        if frame.cursor.0 > 1 {
+            buf.push_str(&tcursor::Right(frame.cursor.0 - 1).to_string());
+        }
+        self.last = Some(frame);
+        buf
+    }
+
+    /// Move to the first row viri drew inline and clear from there down.
+    fn clear_region(&self) -> String {
+        let mut buf = String::new();
+        if let Some(last) = self.last.as_ref().filter(|l| l.cursor.1 > 1) {
+            buf.push_str(&tcursor::Up(last.cursor.1 - 1).to_string());
+        }
+        buf.push('\r');
+        buf.push_str(clear::AfterCursor.as_ref());
+        buf
+    }
+}
+
+impl<W: Write> Drop for Output<W> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+impl<W: Write> Output<RawTerminal<W>> {
+    /// Hand the terminal over to another program, out of raw mode.
+    pub fn suspend(&mut self) -> io::Result<()> {
+        self.release()?;
+        self.out.suspend_raw_mode()
+    }
+
+    /// Take the terminal back after `suspend`. The next frame is drawn
+    /// whole, and inline the output that came meanwhile is printed.
+    pub fn resume(&mut self) -> io::Result<()> {
+        self.out.activate_raw_mode()?;
+        self.last = None;
+        let buf = self.display.map(enter).unwrap_or_default();
+        self.write(&buf)
+    }
+}
+
+/// The escapes that start drawing for `display`.
+fn enter(display: Display) -> String {
+    match display {
+        Display::Inline => String::new(),
+        Display::Fullscreen => MOUSE_ON.to_string(),
+        Display::Altscreen => {
+            format!("{}{}", screen::ToAlternateScreen, MOUSE_ON)
+        }
+    }
 }
 
-pub fn print_buffer(
-    output: &mut RawTerminal<std::io::Stdout>,
+/// The escapes that turn the screen from `last` into `next`; the whole
+/// screen when there is no `last` or its size differs.
+fn diff(last: Option<&Frame>, next: &Frame) -> String {
+    let last = last.filter(|l| l.rows.len() == next.rows.len());
+    let mut buf = String::new();
+    if last.is_none() {
+        buf.push_str(clear::All.as_ref());
+    }
+    for (i, row) in next.rows.iter().enumerate() {
+        match last {
+            Some(last) if last.rows[i] == *row => continue,
+            Some(_) => {}
+            None if row.is_empty() => continue,
+            None => {}
+        }
+        buf.push_str(&tcursor::Goto(1, i as u16 + 1).to_string());
+        buf.push_str(clear::CurrentLine.as_ref());
+        buf.push_str(row);
+    }
+    if !buf.is_empty() || last.is_some_and(|l| l.cursor != next.cursor) {
+        let (col, row) = next.cursor;
+        buf.push_str(&tcursor::Goto(col, row).to_string());
+    }
+    buf
+}
+
+/// The rows under the input line: the status line, then the command line
+/// while one is being typed, with the cursor's row and column in these rows,
+/// or else the last status message. At most `height - 1` rows, keeping the
+/// end.
+fn status_rows(
     state: &State,
-) -> Result<(), std::io::Error> {
-    let mut buf = format!("{}{}", clear::All, tcursor::Goto(1, 1));
-
-    state.history
-        .values()
-        .skip(if state.history.len() > (state.size.1 as usize) {
-            state.history.len() - (state.size.1 as usize)
-        } else {
-            0
-        })
-        .map(|o: &Source| match o {
-            Source::ChildInput(c) => c,
-            Source::ChildOutput(c) => c,
-        })
-        .for_each(|o: &Cursor| {
-            buf.push_str(&o.to_string());
-            buf.push('\n');
-            buf.push('\r');
-        });
-
-    buf.push_str(
-        &tcursor::Goto(
-            1,
-            if state.history.len() > (state.size.1 as usize) {
-                state.size.1
-            } else {
-                state.history.len() as u16 + 1
-            }
-        ).to_string()
-    );
-    buf.push_str(&state.input.to_string());
-    buf.push_str(
-        &tcursor::Goto(
-            state.input.pos() as u16,
-            if state.history.len() > (state.size.1 as usize) {
-                state.size.1
-            } else {
-                state.history.len() as u16 + 1
+    width: usize,
+    height: usize,
+) -> (Vec<String>, Option<(usize, usize)>) {
+    let mut status = Vec::new();
+    if !state.options.statusline.is_empty() {
+        let line = statusline::render(&state.options.statusline, state, width);
+        let cells: Vec<Cell> = line
+            .chars()
+            .map(|c| (c, state.highlights.statusline))
+            .collect();
+        status.push(ansi::render(&cells));
+    }
+
+    let cursor = match state.mode {
+        Mode::Command | Mode::Search { .. } => {
+            let prefix = match state.mode {
+                Mode::Search { backward: false } => "/",
+                Mode::Search { backward: true } => "?",
+                _ => ":",
+            };
+            let before = format!("{}{}", prefix, state.command.before());
+            let (row, col) = layout::position(&before, width);
+            let line = format!("{}{}", prefix, state.command);
+            let cursor = Some((status.len() + row, col));
+            status.extend(layout::wrap(&line, width));
+            cursor
+        }
+        _ => {
+            status.extend(
+                state
+                    .status
+                    .iter()
+                    .flat_map(|s| s.lines())
+                    .flat_map(|l| layout::wrap(l, width)),
+            );
+            None
+        }
+    };
+    let cut = status.len().saturating_sub(height - 1);
+    let cursor = cursor.map(|(row, col)| (row.saturating_sub(cut), col));
+    (status[cut..].to_vec(), cursor)
+}
+
+/// The input line wrapped into at most `rows` rows, and the cursor's row and
+/// column in them. An input taller than that is scrolled to the cursor.
+fn input_rows(
+    state: &State,
+    width: usize,
+    rows: usize,
+) -> (Vec<String>, (usize, usize)) {
+    let (row, col) = layout::position(state.input.before(), width);
+    let height = layout::input_height(&state.input, width).min(rows);
+    let skip = (row + 1).saturating_sub(height);
+    let mut input = wrap(&input_cells(state), width);
+    input.resize(skip + height, String::new());
+    (input.split_off(skip), (row - skip, col))
+}
+
+/// Lay out the transcript, input line and status rows for `state` on the
+/// whole screen.
+fn frame(state: &State) -> Frame {
+    let width = state.size.0.max(1) as usize;
+    let height = state.size.1.max(1) as usize;
+
+    let (status, command_cursor) = status_rows(state, width, height);
+    let rows = height - status.len();
+    let mut frame = Frame {
+        rows: vec![String::new(); height],
+        cursor: (1, 1),
+    };
+
+    let (input, (cursor_row, cursor_col)) = input_rows(state, width, rows);
+
+    // scrolled up, the last history row says how much is hidden below
+    let total = state.history.rows();
+    let scroll = state.scroll.min(total);
+    let space = rows.saturating_sub(input.len() + (scroll > 0) as usize);
+    let mut lines: Vec<String> = Vec::new();
+    for source in state.history.values().rev() {
+        if lines.len() >= scroll + space {
+            break;
+        }
+        let mut cells = cells(source, state);
+        match (source, &state.search) {
+            (Source::ChildInput(_), _) | (_, None) => {}
+            (_, Some(search)) => {
+                let matched = Style {
+                    inverse: true,
+                    ..Style::default()
+                };
+                highlight::paint(&mut cells, &search.pattern, matched);
             }
-        ).to_string()
-    );
-    write!(output, "{}", buf)?;
-    output.flush()?;
-    Ok(())
-
-    // println!("{}\n\r", buf);
-
-    // output.write_all(&mut buf.as_bytes()).await?;
-
-    /*
-       write!(output, "{clear_all}", clear_all = clear::All,)?;
-       for (i, o) in state.output.iter().enumerate() {
-       write!(
-       output,
-       "{output_pos}",
-       output_pos = cursor::Goto(1, 1 + i as u16)
-       )?;
-       write!(output, "{}", o).unwrap();
-       }
-       write!(
-       output,
-       "{state_pos}: {state_mode:?}{command_pos}command: {command}{before_pos}{before}{after}{cursor_pos}",
-       state_pos = cursor::Goto(1, state.size.1 - 2),
-       state_mode = state.mode,
-       command_pos = cursor::Goto(1, state.size.1 - 1),
-       command = format!("{}{}", state.cmd_before, state.cmd_after),
-       before_pos = cursor::Goto(1, state.size.1),
-       before = state.input_before,
-       after = state.input_after.chars().rev().collect::<String>(),
-       cursor_pos = cursor::Goto(state.input_before.len() as u16 + 1, state.size.1),
-       )?;
-       output.flush()?;
-       */
+        }
+        lines.extend(wrap(&cells, width).into_iter().rev());
+    }
+
+    let mut row = 1;
+    for line in lines.iter().skip(scroll).take(space).rev() {
+        frame.rows[row - 1] = line.clone();
+        row += 1;
+    }
+    if scroll > 0 {
+        frame.rows[row - 1] = format!("[+{} lines]", scroll);
+        row += 1;
+    }
+
+    let input_row = row;
+    for line in input {
+        frame.rows[row - 1] = line;
+        row += 1;
+    }
+
+    for (i, line) in status.iter().enumerate() {
+        frame.rows[rows + i] = line.clone();
+    }
+
+    frame.cursor = match command_cursor {
+        Some((r, c)) => (c as u16 + 1, (rows + r) as u16 + 1),
+        None => (cursor_col as u16 + 1, (input_row + cursor_row) as u16),
+    };
+    frame
+}
+
+/// The cells of a transcript entry: the program's own styles over its
+/// group's, then for output the `:match` rules and the prompt.
+fn cells(source: &Source, state: &State) -> Vec<Cell> {
+    let highlights = &state.highlights;
+    let (text, base) = match source {
+        Source::ChildInput(c) => (c, highlights.input),
+        Source::ChildOutput(c) => (c, highlights.output),
+        Source::ChildError(c) => (c, highlights.error),
+    };
+    let mut cells = ansi::parse(&text.to_string());
+    for (_, style) in &mut cells {
+        *style = style.over(base);
+    }
+    if let Source::ChildInput(_) = source {
+        return cells;
+    }
+    for rule in &highlights.rules {
+        highlight::paint(&mut cells, &rule.pattern, rule.style);
+    }
+    if let Some(prompt) = &state.options.prompt {
+        highlight::paint(&mut cells, prompt, highlights.prompt);
+    }
+    cells
+}
+
+/// The input line, colored by the `syntax` lexer, with the partner of the
+/// bracket under the cursor picked out; in insert mode, or of the one just
+/// typed.
+fn input_cells(state: &State) -> Vec<Cell> {
+    let text = state.input.to_string();
+    let lexer = syntax::lexer(&state.options.syntax).unwrap_or(&syntax::NONE);
+    let tokens = lexer.tokens(&text, &state.options.keywords);
+    let mut cells: Vec<Cell> = text
+        .chars()
+        .zip(tokens)
+        .map(|(c, token)| (c, state.highlights.token(token)))
+        .collect();
+
+    let index = state.input.index();
+    let typed = match state.mode {
+        Mode::Insert | Mode::Replace if index > 0 => Some(index - 1),
+        _ => None,
+    };
+    let partner = state
+        .input
+        .partner(index)
+        .or_else(|| typed.and_then(|i| state.input.partner(i)));
+    if let Some((_, style)) = partner.and_then(|i| cells.get_mut(i)) {
+        *style = state.highlights.matchparen.over(*style);
+    }
+    cells
+}
+
+/// Rows of `cells` at most `width` wide, with their styles, breaking at
+/// newlines too.
+fn wrap(cells: &[Cell], width: usize) -> Vec<String> {
+    layout::rows(cells, width)
+        .iter()
+        .map(|row| ansi::render(row))
+        .collect()
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cursor::Cursor, keys::KeyMap};
+
+    fn frame(rows: &[&str], cursor: (u16, u16)) -> Frame {
+        Frame {
+            rows: rows.iter().map(|r| r.to_string()).collect(),
+            cursor,
+        }
+    }
+
+    #[test]
+    pub fn diff_changed_rows() {
+        let last = frame(&["sqlite> ", "", "select 1;"], (10, 3));
+        let next = frame(&["sqlite> ", "1", "select 1;"], (10, 3));
+        assert_eq!(diff(Some(&last), &last), "");
+        assert_eq!(
+            diff(Some(&last), &next),
+            format!(
+                "{}{}1{}",
+                tcursor::Goto(1, 2),
+                clear::CurrentLine,
+                tcursor::Goto(10, 3)
+            )
+        );
+        let moved = frame(&["sqlite> ", "", "select 1;"], (1, 3));
+        assert_eq!(diff(Some(&last), &moved), tcursor::Goto(1, 3).to_string());
+    }
+
+    #[test]
+    pub fn restore_on_drop() {
+        let mut written = Vec::new();
+        let mut state = State::new("cat".to_string(), (20, 4), KeyMap::new());
+        state.options.display = Display::Altscreen;
+        {
+            let mut output = Output::new(&mut written);
+            output.draw(&state).unwrap();
+        }
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.starts_with(&enter(Display::Altscreen)));
+        assert!(written.ends_with(&format!(
+            "{}{}{}",
+            MOUSE_OFF,
+            screen::ToMainScreen,
+            Shape::Default.escape()
+        )));
+    }
+
+    #[test]
+    pub fn diff_redraws_without_last() {
+        let next = frame(&["a", ""], (2, 1));
+        assert_eq!(
+            diff(None, &next),
+            format!(
+                "{}{}{}a{}",
+                clear::All,
+                tcursor::Goto(1, 1),
+                clear::CurrentLine,
+                tcursor::Goto(2, 1)
+            )
+        );
+    }
+
+    #[test]
+    pub fn clear_inline_region() {
+        let mut output = Output::new(Vec::new());
+        let clear = format!("\r{}", clear::AfterCursor);
+        assert_eq!(output.clear_region(), clear);
+        output.last = Some(frame(&["a", "b", "c"], (1, 1)));
+        assert_eq!(output.clear_region(), clear);
+        output.last = Some(frame(&["a", "b", "c"], (4, 3)));
+        assert_eq!(
+            output.clear_region(),
+            format!("{}{}", tcursor::Up(2), clear)
+        );
+    }
+
+    #[test]
+    pub fn inline_prints_new_entries() {
+        let mut state = State::new("cat".to_string(), (20, 4), KeyMap::new());
+        let line = |text: &str| Cursor::from(text.to_string(), String::new());
+        state.history.push(Source::ChildOutput(line("hello")));
+        let mut output = Output::new(Vec::new());
+
+        let clear = output.clear_region();
+        let first = output.inline(&state);
+        assert!(first.starts_with(&format!("{}hello\r\n", clear)));
+        assert_eq!(output.printed, 1);
+        // nothing changed, nothing is written
+        assert_eq!(output.inline(&state), "");
+
+        // only the new entry is printed, over the old input region
+        state.history.push(Source::ChildOutput(line("world")));
+        let clear = output.clear_region();
+        let second = output.inline(&state);
+        assert!(second.starts_with(&format!("{}world\r\n", clear)));
+        assert!(!second.contains("hello"));
+        assert_eq!(output.printed, 2);
+    }
 }
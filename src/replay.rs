@@ -0,0 +1,254 @@
+use super::{
+    ansi,
+    config::{expand_path, Options},
+    cursor::Cursor,
+    state::{Mode, State},
+};
+use anyhow::{anyhow, bail, Result};
+use std::{collections::VecDeque, fs, time::Instant};
+
+/// What `:replay` and the `Replay*` functions do to a replay.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Control {
+    /// queue the lines of a file
+    Start(String),
+    Pause,
+    Resume,
+    /// pause if running, resume if paused
+    Toggle,
+    /// send the next line now, then stay paused
+    Step,
+    /// drop the next line without sending it
+    Skip,
+    Abort,
+}
+
+/// A script of inputs being fed to the child one line at a time. The next
+/// line is put on the input line first, so a paused replay can be edited
+/// before it is sent.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    queue: VecDeque<String>,
+    total: usize,
+    paused: bool,
+    /// the next line is on the input line
+    staged: bool,
+    /// when the last line was sent
+    sent: Option<Instant>,
+    /// the child has shown its prompt since then
+    prompted: bool,
+}
+
+impl Replay {
+    /// Queue every non-blank line of `text`.
+    pub fn new(text: &str) -> Self {
+        let queue: VecDeque<String> = text
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(String::from)
+            .collect();
+        Replay {
+            total: queue.len(),
+            queue,
+            paused: false,
+            staged: false,
+            sent: None,
+            prompted: false,
+        }
+    }
+
+    pub fn progress(&self) -> String {
+        format!(
+            "replay {}/{}{}",
+            self.total - self.queue.len(),
+            self.total,
+            if self.paused { " (paused)" } else { "" }
+        )
+    }
+
+    /// Watch the child's output for its prompt.
+    pub fn saw_output(&mut self, line: &str, options: &Options) {
+        let line = ansi::strip(line);
+        if options.prompt.as_ref().is_some_and(|p| p.is_match(&line)) {
+            self.prompted = true;
+        }
+    }
+
+    /// An input line was sent; if one was staged, move on, even if it was
+    /// edited first.
+    pub fn sent(&mut self) {
+        if self.staged {
+            self.queue.pop_front();
+        }
+        self.staged = false;
+        self.sent = Some(Instant::now());
+        self.prompted = false;
+    }
+
+    /// Whether the child is ready for the next line: `replaydelay` has
+    /// passed since the last one, and the prompt has come back if `prompt`
+    /// is set.
+    fn ready(&self, options: &Options) -> bool {
+        match self.sent {
+            None => true,
+            Some(sent) => {
+                sent.elapsed() >= options.replay_delay()
+                    && (options.prompt.is_none() || self.prompted)
+            }
+        }
+    }
+}
+
+/// Move a replay along: stage the next line once the input line is free, and
+/// send it when the child is ready. Returns whether anything changed.
+pub fn tick(mut state: State) -> (State, bool) {
+    let idle = matches!(state.mode, Mode::Normal) && state.pending.is_none();
+    let replay = match state.replay.as_mut() {
+        Some(replay) if idle => replay,
+        _ => return (state, false),
+    };
+
+    let line = match replay.queue.front() {
+        Some(line) => line.clone(),
+        None => {
+            state.status = Some(format!("replay done: {}", replay.progress()));
+            state.replay = None;
+            return (state, true);
+        }
+    };
+
+    if !replay.staged {
+        // leave whatever the user is typing alone
+        if !state.input.is_empty() {
+            return (state, false);
+        }
+        replay.staged = true;
+        state.input = Cursor::from(line, String::new());
+        return (state, true);
+    }
+
+    if !replay.paused && replay.ready(&state.options) {
+        state.mode = Mode::Execute;
+        return (state, true);
+    }
+    (state, false)
+}
+
+/// Apply `ctl`, returning the text to show in the status line.
+pub fn control(ctl: Control, state: &mut State) -> Result<Option<String>> {
+    if let Control::Start(path) = ctl {
+        let text = fs::read_to_string(expand_path(&path))
+            .map_err(|e| anyhow!("{}: {}", path, e))?;
+        let replay = Replay::new(&text);
+        let progress = replay.progress();
+        state.replay = Some(replay);
+        return Ok(Some(progress));
+    }
+
+    let replay = match state.replay.as_mut() {
+        Some(replay) => replay,
+        None => bail!("not replaying"),
+    };
+    match ctl {
+        Control::Start(_) => unreachable!(),
+        Control::Pause => replay.paused = true,
+        Control::Resume => replay.paused = false,
+        Control::Toggle => replay.paused = !replay.paused,
+        Control::Step => {
+            let line = match replay.queue.front() {
+                Some(line) => line.clone(),
+                None => bail!("nothing to replay"),
+            };
+            if !replay.staged && !state.input.is_empty() {
+                bail!("input line not empty");
+            }
+            replay.paused = true;
+            if !replay.staged {
+                replay.staged = true;
+                state.input = Cursor::from(line, String::new());
+            }
+            state.mode = Mode::Execute;
+        }
+        Control::Skip => {
+            replay.queue.pop_front();
+            if replay.staged {
+                replay.staged = false;
+                state.input = Cursor::new();
+            }
+        }
+        Control::Abort => {
+            if replay.staged {
+                state.input = Cursor::new();
+            }
+            state.replay = None;
+            return Ok(Some("replay aborted".to_string()));
+        }
+    }
+    Ok(Some(replay.progress()))
+}
+
+/// Apply `ctl` from a key binding.
+pub fn run(ctl: Control, mut state: State) -> State {
+    state.status = match control(ctl, &mut state) {
+        Ok(message) => message,
+        Err(e) => Some(format!("E: {}", e)),
+    };
+    state
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    pub fn replay_queue() {
+        let mut replay = Replay::new("select 1;\n\n  \n.tables\n");
+        assert_eq!(replay.progress(), "replay 0/2");
+        replay.staged = true;
+        replay.sent();
+        assert_eq!(replay.queue.front().unwrap(), ".tables");
+        assert_eq!(replay.progress(), "replay 1/2");
+
+        // an edited staged line replaces the queued one
+        replay.staged = true;
+        replay.sent();
+        assert!(!replay.staged);
+        assert_eq!(replay.progress(), "replay 2/2");
+
+        // a line typed with nothing staged leaves the queue alone
+        replay.sent();
+        assert_eq!(replay.progress(), "replay 2/2");
+    }
+
+    #[test]
+    pub fn step_past_end() {
+        let mut state =
+            State::new("sqlite3".to_string(), (80, 24), Default::default());
+        state.replay = Some(Replay::new(""));
+        let err = control(Control::Step, &mut state).unwrap_err();
+        assert_eq!(err.to_string(), "nothing to replay");
+        assert!(matches!(state.mode, Mode::Normal));
+    }
+
+    #[test]
+    pub fn replay_waits_for_prompt() {
+        let mut options = Options::new();
+        options.replaydelay = 0;
+        options.prompt = Some(Regex::new("^sqlite> $").unwrap());
+        let mut replay = Replay::new("select 1;\n.tables\n");
+        assert!(replay.ready(&options));
+
+        replay.staged = true;
+        replay.sent();
+        assert!(!replay.ready(&options));
+        replay.saw_output("1", &options);
+        assert!(!replay.ready(&options));
+        replay.saw_output("sqlite> ", &options);
+        assert!(replay.ready(&options));
+    }
+}
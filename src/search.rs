@@ -0,0 +1,186 @@
+use super::{
+    ansi,
+    config::Display,
+    history::{History, Source},
+    state::{half_page, page, State},
+};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+
+/// A search through the child's output, started with `/` or `?`. Unlike
+/// history navigation it never touches the input line.
+#[derive(Clone, Debug)]
+pub struct Search {
+    pub pattern: Regex,
+    pub backward: bool,
+    /// the history index of the entry last matched
+    current: Option<usize>,
+}
+
+impl Search {
+    pub fn new(pattern: &str, backward: bool) -> Result<Self> {
+        Ok(Search {
+            pattern: Regex::new(pattern)?,
+            backward,
+            current: None,
+        })
+    }
+
+    /// The next output entry matching, before `from` when searching
+    /// backward and after it otherwise, wrapping around the ends. Returns
+    /// its index and whether the search wrapped.
+    fn find(
+        &self,
+        history: &History,
+        from: usize,
+        backward: bool,
+    ) -> Option<(usize, bool)> {
+        let matches: Vec<usize> = history
+            .values()
+            .enumerate()
+            .filter_map(|(i, source)| match source {
+                Source::ChildOutput(c) | Source::ChildError(c)
+                    if self.pattern.is_match(&ansi::strip(&c.to_string())) =>
+                {
+                    Some(i)
+                }
+                _ => None,
+            })
+            .collect();
+        let found = if backward {
+            matches.iter().rev().find(|&&i| i < from)
+        } else {
+            matches.iter().find(|&&i| i > from)
+        };
+        match found {
+            Some(&i) => Some((i, false)),
+            None if backward => matches.last().map(|&i| (i, true)),
+            None => matches.first().map(|&i| (i, true)),
+        }
+    }
+}
+
+/// Start a search for `pattern`, or repeat the last one if it is empty.
+pub fn start(pattern: &str, backward: bool, mut state: State) -> State {
+    let search = match (pattern, state.search.take()) {
+        ("", Some(last)) => Ok(Search {
+            backward,
+            current: None,
+            ..last
+        }),
+        ("", None) => Err(anyhow!("no previous search")),
+        (pattern, _) => Search::new(pattern, backward),
+    };
+    match search {
+        Ok(search) => {
+            state.search = Some(search);
+            next(state, false)
+        }
+        Err(e) => {
+            state.status = Some(format!("E: {}", e));
+            state
+        }
+    }
+}
+
+/// Go to the next match of the current search, or the previous one if
+/// `reverse`, like vim's `n` and `N`.
+pub fn next(mut state: State, reverse: bool) -> State {
+    let search = match &state.search {
+        Some(search) => search,
+        None => {
+            state.status = Some("E: no previous search".to_string());
+            return state;
+        }
+    };
+    let backward = search.backward != reverse;
+
+    // a new search starts from the bottom of the viewport
+    let from = search
+        .current
+        .unwrap_or_else(|| state.history.entries_above(state.scroll));
+    let found = search.find(&state.history, from, backward);
+    let pattern = search.pattern.to_string();
+    match found {
+        Some((index, wrapped)) => {
+            if let Some(search) = state.search.as_mut() {
+                search.current = Some(index);
+            }
+            // inline, the scrollback is the terminal's, so show the line
+            if state.options.display == Display::Inline {
+                state.status =
+                    state.history.values().nth(index).map(|s| match s {
+                        Source::ChildInput(c)
+                        | Source::ChildOutput(c)
+                        | Source::ChildError(c) => ansi::strip(&c.to_string()),
+                    });
+            }
+            if wrapped {
+                state.status = Some(if backward {
+                    "search hit TOP, continuing at BOTTOM".to_string()
+                } else {
+                    "search hit BOTTOM, continuing at TOP".to_string()
+                });
+            }
+            scroll_to(state, index)
+        }
+        None => {
+            state.status = Some(format!("E: pattern not found: {}", pattern));
+            state
+        }
+    }
+}
+
+/// Scroll so that the history entry at `index` is mid-screen.
+fn scroll_to(mut state: State, index: usize) -> State {
+    let top = state.history.rows().saturating_sub(page(&state) as usize);
+    state.scroll = state
+        .history
+        .rows_from(index)
+        .saturating_sub(half_page(&state) as usize)
+        .min(top);
+    state
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::Cursor;
+
+    fn history(lines: &[(bool, &str)]) -> History {
+        let mut history = History::new();
+        for (input, line) in lines {
+            let c = Cursor::from(line.to_string(), String::new());
+            history.push(match input {
+                true => Source::ChildInput(c),
+                false => Source::ChildOutput(c),
+            });
+        }
+        history
+    }
+
+    #[test]
+    pub fn search_output_only() {
+        let history = history(&[
+            (true, "select 'error';"),
+            (false, "error"),
+            (false, "ok"),
+            (true, "select 'error';"),
+            (false, "error"),
+        ]);
+        let search = Search::new("err", true).unwrap();
+        assert_eq!(search.find(&history, 5, true), Some((4, false)));
+        assert_eq!(search.find(&history, 4, true), Some((1, false)));
+        assert_eq!(search.find(&history, 1, true), Some((4, true)));
+        assert_eq!(search.find(&history, 1, false), Some((4, false)));
+        assert_eq!(search.find(&history, 4, false), Some((1, true)));
+        assert_eq!(
+            Search::new("warn", false).unwrap().find(&history, 0, false),
+            None
+        );
+    }
+}
@@ -1,12 +1,28 @@
-use super::cursor::Cursor;
+use super::{
+    child::Process,
+    command,
+    config::{Display, Options},
+    cursor::{Cursor, PAIRS},
+    highlight::Highlights,
+    history::History,
+    keys::{KeyMap, Lookup},
+    replay::{self, Control, Replay},
+    search::{self, Search},
+    syntax,
+};
 use phf::phf_map;
-use std::{clone, collections::HashMap, slice::Iter};
-use termion::event::{Event, Key};
+use std::time::Instant;
+use termion::event::{Event, Key, MouseButton, MouseEvent};
+
+/// The largest count typed before a mapping, so a slip of the finger cannot
+/// keep viri busy running it.
+const MAX_COUNT: usize = 9999;
 
 static FUNCTIONS: phf::Map<&'static str, fn(State) -> State> = phf_map! {
     "Enter" => enter,
     "Insert" => insert,
     "InsertStart" => insert_start,
+    "Replace" => replace,
     "Append" => append,
     "AppendEnd" => append_end,
     "DeletePosInsert" => delete_pos_insert,
@@ -35,132 +51,119 @@ static FUNCTIONS: phf::Map<&'static str, fn(State) -> State> = phf_map! {
         state.input.left_all();
         state
     },
+    "CursorMatch" => |mut state: State| {
+        if let Some(target) = state.input.bracket_target() {
+            state.input.move_to(target);
+        }
+        state
+    },
+    "DeleteMatch" => delete_match,
+    "ChangeMatch" => |state| insert(delete_match(state)),
     "ClearAfterCursor" => clear_after_insert,
+    "EditInput" => |mut state: State| {
+        state.mode = Mode::Edit;
+        state
+    },
+    "Normal" => normal,
+    "ReplayPause" => |state| replay::run(Control::Toggle, state),
+    "ReplayStep" => |state| replay::run(Control::Step, state),
+    "ReplaySkip" => |state| replay::run(Control::Skip, state),
+    "ReplayAbort" => |state| replay::run(Control::Abort, state),
+    "SearchNext" => |state| search::next(state, false),
+    "SearchPrev" => |state| search::next(state, true),
     "Quit" => quit,
 };
 
 #[derive(Clone, Debug)]
 pub struct State {
+    pub program: String,
     pub mode: Mode,
     pub size: (u16, u16),
     pub input: Cursor,
     pub command: Cursor,
-    pub keys: HashMap<String, String>,
+    pub pending: Option<Instant>,
+    pub status: Option<String>,
+    pub options: Options,
+    pub keys: KeyMap,
+    pub insert_keys: KeyMap,
     pub history: History,
+    pub replay: Option<Replay>,
+    /// how many rows the viewport is scrolled up from the bottom
+    pub scroll: usize,
+    /// the last output search, highlighted until Esc
+    pub search: Option<Search>,
+    /// digits typed before a normal mode mapping
+    pub count: Option<usize>,
+    pub child: Process,
+    pub highlights: Highlights,
 }
 
-#[derive(Clone, Debug)]
-pub enum Source {
-    ChildInput(Cursor),
-    ChildOutput(Cursor),
-}
-
-#[derive(Clone, Debug)]
-pub struct History {
-    history: Vec<Source>,
-    pos: usize,
-}
-
-impl History {
-    pub fn new() -> Self {
-        let history = Vec::with_capacity(100);
-        History { history, pos: 0 }
-    }
-
-    pub fn push(&mut self, value: Source) {
-        self.history.push(value);
-    }
-
-    pub fn values(&self) -> HistoryIterator {
-        HistoryIterator {
-            iter: self.history.iter(),
-        }
-    }
-
-    pub fn get_prev(&mut self) -> Cursor {
-        // if self.pos < self.history.len() {
-        //     self.pos += 1;
-        // }
-        let hist = self
-            .history
-            .iter()
-            .filter_map(|h| match h {
-                Source::ChildInput(c) => Some(c),
-                Source::ChildOutput(_) => None,
-            })
-            .rev()
-            .take(self.pos + 1)
-            .collect::<Vec<&Cursor>>();
-
-        if self.pos < hist.len() {
-            self.pos += 1;
-        }
-        (*hist.last().unwrap_or(&&Cursor::new())).clone()
-    }
-
-    pub fn get_next(&mut self) -> Cursor {
-        if self.pos > 0 {
-            self.pos -= 1;
-        }
-        self.history
-            .iter()
-            .filter_map(|h| match h {
-                Source::ChildInput(c) => Some(c),
-                Source::ChildOutput(_) => None,
-            })
-            .rev()
-            .take(self.pos)
-            .last()
-            .unwrap_or(&Cursor::new())
-            .clone()
-    }
-
-    pub fn len(&self) -> usize {
-        self.history.len()
-    }
-}
-
-pub struct HistoryIterator<'a> {
-    iter: std::slice::Iter<'a, Source>,
-}
-
-impl<'a> Iterator for HistoryIterator<'a> {
-    type Item = &'a Source;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next()
-    }
-}
-
-/*
-impl IntoIterator for History {
-    type Item = Cursor;
-    type IntoIter = std::vec::IntoIter<Self::Item>;
-
-    fn into_iter(self) -> Self::IntoIter {
-        self.history.into_iter()
+impl State {
+    pub fn new(program: String, size: (u16, u16), keys: KeyMap) -> Self {
+        let mut state = State {
+            program,
+            mode: Mode::Normal,
+            size,
+            input: Cursor::new(),
+            command: Cursor::new(),
+            pending: None,
+            status: None,
+            options: Options::new(),
+            keys,
+            insert_keys: KeyMap::new(),
+            history: History::new(),
+            replay: None,
+            scroll: 0,
+            search: None,
+            count: None,
+            child: Process::default(),
+            highlights: Highlights::default(),
+        };
+        state.history.set_width(size.0 as usize);
+        state
     }
 }
-*/
 
 #[derive(Clone, Debug)]
 pub enum Mode {
     Execute,
+    /// editing the input in `$VISUAL` or `$EDITOR`
+    Edit,
     Command,
+    /// typing an output search pattern after `/` or `?`
+    Search {
+        backward: bool,
+    },
     Insert,
+    /// typing over the input, after `R`
+    Replace,
     Normal,
     Quit,
 }
 
 pub fn normal_mode(evt: Event, mut state: State) -> State {
+    // a count typed before one of the built-in keys is dropped
+    if let Event::Key(
+        Key::Char('\n' | ':' | '/' | '?' | 'k' | 'j')
+        | Key::Up
+        | Key::Down
+        | Key::Ctrl('u' | 'd' | 'b' | 'f'),
+    ) = evt
+    {
+        state.count = None;
+    }
+
     match evt {
         Event::Key(Key::Esc) => {
             state.command = Cursor::new();
+            state.pending = None;
+            state.count = None;
+            state.search = None;
             state
         }
 
         Event::Key(Key::Char('\n')) => {
-            state.history.pos = 0;
+            state.history.reset();
             state.mode = Mode::Execute;
             state
         }
@@ -170,30 +173,83 @@ pub fn normal_mode(evt: Event, mut state: State) -> State {
             state
         }
 
-        Event::Key(Key::Char('k')) => {
-            // state.history.push(state.input);
-            state.input = state.history.get_prev();
+        Event::Key(Key::Char(c @ '/')) | Event::Key(Key::Char(c @ '?')) => {
+            state.mode = Mode::Search {
+                backward: c == '?',
+            };
+            state
+        }
+
+        // up and down move between the lines of a multi-line input, and
+        // through the history from its first and last lines
+        Event::Key(Key::Char('k')) | Event::Key(Key::Up) => {
+            if !state.input.move_line(false) {
+                state.input = state.history.get_prev(&state.input);
+            }
+            state
+        }
+
+        Event::Key(Key::Char('j')) | Event::Key(Key::Down) => {
+            if !state.input.move_line(true) {
+                state.input = state.history.get_next(&state.input);
+            }
             state
         }
 
-        Event::Key(Key::Char('j')) => {
-            // state.history.push(state.input);
-            state.input = state.history.get_next();
+        Event::Key(Key::Ctrl('u')) => scroll(state, half_page),
+        Event::Key(Key::Ctrl('d')) => scroll(state, |s| -half_page(s)),
+        Event::Key(Key::Ctrl('b')) => scroll(state, page),
+        Event::Key(Key::Ctrl('f')) => scroll(state, |s| -page(s)),
+
+        // digits before a mapping are a count of times to run it, as in vim
+        Event::Key(Key::Char(c @ '0'..='9'))
+            if state.command.is_empty()
+                && (c != '0' || state.count.is_some())
+                && state.keys.lookup(&c.to_string()) == Lookup::Invalid =>
+        {
+            let digit = c.to_digit(10).unwrap_or(0) as usize;
+            let count = state.count.unwrap_or(0) * 10 + digit;
+            state.count = Some(count.min(MAX_COUNT));
             state
         }
 
         Event::Key(Key::Char(c)) => {
+            let typed = state.command.to_string();
             state.command.insert(c);
-            if let Some(cmd) = state.keys.get(&state.command.to_string()) {
-                match FUNCTIONS.get::<str>(cmd) {
-                    Some(f) => {
-                        state.command = Cursor::new();
-                        state = f(state);
+            state.pending = None;
+            match state.keys.lookup(&state.command.to_string()) {
+                Lookup::Complete(cmd) => {
+                    state.command = Cursor::new();
+                    repeat(&cmd, state)
+                }
+
+                Lookup::Ambiguous(_) => {
+                    state.pending = Some(Instant::now());
+                    state
+                }
+
+                // a shorter binding is still waiting on the timeout
+                Lookup::Prefix if state.keys.longest(&typed).is_some() => {
+                    state.pending = Some(Instant::now());
+                    state
+                }
+
+                Lookup::Prefix => state,
+
+                Lookup::Invalid => {
+                    state.command = Cursor::new();
+                    // a shorter binding was waiting on the timeout; the new
+                    // key settles it, so run it and start over with the keys
+                    // after it
+                    if let Some((len, cmd)) = state.keys.longest(&typed) {
+                        state = repeat(&cmd, state);
+                        let rest: String = typed.chars().skip(len).collect();
+                        return take_keys(&format!("{}{}", rest, c), state);
                     }
-                    None => unreachable!("function not found"),
+                    state.count = None;
+                    state
                 }
             }
-            state
         }
 
         _ => {
@@ -202,6 +258,7 @@ pub fn normal_mode(evt: Event, mut state: State) -> State {
     }
 }
 
+/// Edit an ex command after `:`, or an output search after `/` or `?`.
 pub fn command_mode(evt: Event, mut state: State) -> State {
     match evt {
         Event::Key(Key::Esc) => {
@@ -210,20 +267,16 @@ pub fn command_mode(evt: Event, mut state: State) -> State {
         }
 
         Event::Key(Key::Char('\n')) => {
-            match state.keys.get(&state.command.to_string()) {
-                Some(cmd) => match FUNCTIONS.get::<str>(cmd) {
-                    Some(f) => {
-                        state.command = Cursor::new();
-                        state = f(state);
-                    }
-                    None => unreachable!(),
-                },
+            let line = state.command.to_string();
+            state.command = Cursor::new();
+            state = match std::mem::replace(&mut state.mode, Mode::Normal) {
+                Mode::Search { backward } => search::start(&line, backward, state),
+                _ => command::execute(&line, state),
+            };
+        }
 
-                None => {
-                    state.command = Cursor::new();
-                    state.mode = Mode::Normal;
-                }
-            }
+        Event::Key(Key::Backspace) if state.command.is_empty() => {
+            state.mode = Mode::Normal;
         }
 
         Event::Key(Key::Backspace) => {
@@ -254,16 +307,36 @@ pub fn command_mode(evt: Event, mut state: State) -> State {
 pub fn insert_mode(evt: Event, mut state: State) -> State {
     match evt {
         Event::Key(Key::Esc) => {
-            state.input.left_char();
-            state.mode = Mode::Normal;
+            state.command = Cursor::new();
+            state.pending = None;
+            state = normal(state);
+        }
+
+        Event::Key(Key::Char('\n'))
+            if state.options.multiline
+                && syntax::incomplete(
+                    &state.input.to_string(),
+                    &state.options,
+                ) =>
+        {
+            state.input.insert('\n');
         }
 
         Event::Key(Key::Char('\n')) => {
-            state.history.pos = 0;
+            state.history.reset();
             state.mode = Mode::Execute;
         }
 
         Event::Key(Key::Backspace) => {
+            // with autopairs, an empty pair goes as one
+            let prev = state.input.before().chars().next_back();
+            let close = prev.and_then(closing);
+            if state.options.autopairs
+                && close.is_some()
+                && close == state.input.current()
+            {
+                state.input.delete();
+            }
             state.input.backspace();
         }
 
@@ -279,8 +352,56 @@ pub fn insert_mode(evt: Event, mut state: State) -> State {
             state.input.right_char();
         }
 
+        Event::Key(Key::Up) => {
+            if !state.input.move_line(false) {
+                state.input = state.history.get_prev(&state.input);
+            }
+        }
+
+        Event::Key(Key::Down) => {
+            if !state.input.move_line(true) {
+                state.input = state.history.get_next(&state.input);
+            }
+        }
+
         Event::Key(Key::Char(c)) => {
-            state.input.insert(c);
+            state.command.insert(c);
+            state.pending = None;
+            match state.insert_keys.lookup(&state.command.to_string()) {
+                Lookup::Complete(cmd) => {
+                    state.command = Cursor::new();
+                    state = run(&cmd, state);
+                }
+
+                Lookup::Ambiguous(_) | Lookup::Prefix => {
+                    state.pending = Some(Instant::now());
+                }
+
+                Lookup::Invalid => {
+                    state.command.backspace();
+                    let typed = state.command.to_string();
+                    state.command = Cursor::new();
+                    if typed.is_empty() {
+                        return type_char(c, state);
+                    }
+
+                    // the keys typed so far were not a mapping after all:
+                    // run the longest one they start with or insert them as
+                    // text, then start over with the keys after it
+                    return match state.insert_keys.longest(&typed) {
+                        Some((len, cmd)) => {
+                            state = run(&cmd, state);
+                            let rest: String =
+                                typed.chars().skip(len).collect();
+                            take_keys(&format!("{}{}", rest, c), state)
+                        }
+                        None => {
+                            typed.chars().for_each(|t| state.input.insert(t));
+                            take_keys(&c.to_string(), state)
+                        }
+                    };
+                }
+            }
         }
 
         _ => {
@@ -290,12 +411,167 @@ pub fn insert_mode(evt: Event, mut state: State) -> State {
     state
 }
 
+/// Quotes that `autopairs` closes, besides the brackets.
+const QUOTES: &[char] = &['"', '\'', '`'];
+
+/// The character `autopairs` closes `c` with.
+fn closing(c: char) -> Option<char> {
+    match PAIRS.iter().find(|(open, _)| *open == c) {
+        Some((_, close)) => Some(*close),
+        None => QUOTES.contains(&c).then_some(c),
+    }
+}
+
+/// Insert a typed character. With `autopairs`, an opening bracket or quote
+/// comes with its closing one, and typing that closing character steps over
+/// it instead.
+fn type_char(c: char, mut state: State) -> State {
+    let closes =
+        PAIRS.iter().any(|(_, close)| *close == c) || QUOTES.contains(&c);
+    if !state.options.autopairs {
+        state.input.insert(c);
+    } else if closes && state.input.current() == Some(c) {
+        state.input.right_char();
+    } else if let Some(close) = closing(c) {
+        state.input.insert(c);
+        state.input.insert(close);
+        state.input.left_char();
+    } else {
+        state.input.insert(c);
+    }
+    state
+}
+
+/// Resolve a pending key sequence once `timeoutlen` has passed without
+/// another key arriving, like vim: the longest mapping the keys start with
+/// runs and the keys after it are taken again. In insert mode keys without
+/// a mapping are inserted as text.
+pub fn check_timeout(mut state: State) -> State {
+    match state.pending {
+        Some(start) if start.elapsed() >= state.options.timeout() => {
+            state.pending = None;
+            let keys = state.command.to_string();
+            state.command = Cursor::new();
+            let found = match state.mode {
+                Mode::Insert => state.insert_keys.longest(&keys),
+                _ => state.keys.longest(&keys),
+            };
+            let (len, cmd) = match (found, &state.mode) {
+                (Some(found), _) => found,
+                (None, Mode::Insert) => {
+                    keys.chars().for_each(|c| state.input.insert(c));
+                    return state;
+                }
+                (None, _) => {
+                    state.count = None;
+                    return state;
+                }
+            };
+            state = match state.mode {
+                Mode::Insert => run(&cmd, state),
+                _ => repeat(&cmd, state),
+            };
+            take_keys(&keys.chars().skip(len).collect::<String>(), state)
+        }
+        _ => state,
+    }
+}
+
+/// Type `keys` again in whatever mode the last mapping left.
+fn take_keys(keys: &str, mut state: State) -> State {
+    for c in keys.chars() {
+        let evt = Event::Key(Key::Char(c));
+        state = match state.mode {
+            Mode::Normal => normal_mode(evt, state),
+            Mode::Insert => insert_mode(evt, state),
+            _ => state,
+        };
+    }
+    state
+}
+
+/// Scroll the viewport with the mouse wheel, in any mode.
+pub fn mouse(evt: MouseEvent, state: State) -> State {
+    match evt {
+        MouseEvent::Press(MouseButton::WheelUp, _, _) => scroll(state, |_| 3),
+        MouseEvent::Press(MouseButton::WheelDown, _, _) => {
+            scroll(state, |_| -3)
+        }
+        _ => state,
+    }
+}
+
+pub fn is_function(name: &str) -> bool {
+    FUNCTIONS.contains_key(name)
+}
+
+fn run(cmd: &str, state: State) -> State {
+    match FUNCTIONS.get(cmd) {
+        Some(f) => f(state),
+        None => unreachable!("function not found"),
+    }
+}
+
+/// Run a normal mode mapping as many times as the count typed before it.
+fn repeat(cmd: &str, mut state: State) -> State {
+    for _ in 0..state.count.take().unwrap_or(1) {
+        state = run(cmd, state);
+    }
+    state
+}
+
 fn enter(mut state: State) -> State {
-    state.history.pos = 0;
+    state.history.reset();
     state.mode = Mode::Execute;
     state
 }
 
+/*
+ * scrolling the viewport
+ */
+
+/// Rows of history in a full screen, leaving the input and status rows.
+pub fn page(state: &State) -> isize {
+    (state.size.1 as isize - 2).max(1)
+}
+
+pub fn half_page(state: &State) -> isize {
+    (page(state) / 2).max(1)
+}
+
+/// Scroll up by `rows(state)` rows, or down if negative, keeping at least a
+/// page of history on screen.
+fn scroll(mut state: State, rows: fn(&State) -> isize) -> State {
+    // inline, the scrollback is the terminal's
+    if state.options.display == Display::Inline {
+        return state;
+    }
+    let total = state.history.rows();
+    let top = total.saturating_sub(page(&state) as usize);
+    let scroll = state.scroll as isize + rows(&state);
+    state.scroll = scroll.clamp(0, top as isize) as usize;
+    state
+}
+
+/// Type over the input line: each character replaces the one under the
+/// cursor, extending the line at its end.
+pub fn replace_mode(evt: Event, mut state: State) -> State {
+    match evt {
+        Event::Key(Key::Esc) => state = normal(state),
+        Event::Key(Key::Char('\n')) => {
+            state.history.reset();
+            state.mode = Mode::Execute;
+        }
+        Event::Key(Key::Backspace) | Event::Key(Key::Left) => {
+            state.input.left_char();
+        }
+        Event::Key(Key::Right) => state.input.right_char(),
+        Event::Key(Key::Char(c)) => state.input.replace(c),
+        _ => {}
+    }
+    state
+}
+
 /*
  * into insert mode
  */
@@ -335,12 +611,38 @@ fn delete_line_insert(mut state: State) -> State {
     state
 }
 
+fn replace(mut state: State) -> State {
+    state.mode = Mode::Replace;
+    state
+}
+
+/// Delete from the cursor to where `%` would go, that included.
+fn delete_match(mut state: State) -> State {
+    if let Some(target) = state.input.bracket_target() {
+        let index = state.input.index();
+        state
+            .input
+            .delete_range(index.min(target), index.max(target) + 1);
+    }
+    state
+}
+
 fn clear_after_insert(mut state: State) -> State {
     state.input.clear_after();
     state.mode = Mode::Insert;
     state
 }
 
+/*
+ * back to normal mode
+ */
+
+fn normal(mut state: State) -> State {
+    state.input.left_char();
+    state.mode = Mode::Normal;
+    state
+}
+
 /*
  * quit application
  */
@@ -354,6 +656,64 @@ fn quit(mut state: State) -> State {
  * Tests
  */
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Source;
+
+    fn output(lines: usize, size: (u16, u16)) -> State {
+        let mut state =
+            State::new("cat".to_string(), size, Default::default());
+        state.options.display = Display::Fullscreen;
+        for i in 0..lines {
+            let line = Cursor::from(i.to_string(), String::new());
+            state.history.push(Source::ChildOutput(line));
+        }
+        state
+    }
+
+    #[test]
+    pub fn page_size() {
+        assert_eq!(page(&output(0, (80, 10))), 8);
+        assert_eq!(half_page(&output(0, (80, 10))), 4);
+        // a tiny terminal still scrolls a row at a time
+        assert_eq!(page(&output(0, (80, 2))), 1);
+        assert_eq!(half_page(&output(0, (80, 2))), 1);
+    }
+
+    #[test]
+    pub fn scroll_clamps() {
+        let state = scroll(output(20, (80, 10)), page);
+        assert_eq!(state.scroll, 8);
+        // a page of history stays on screen
+        let state = scroll(scroll(state, page), page);
+        assert_eq!(state.scroll, 12);
+        let state = scroll(state, |s| -half_page(s));
+        assert_eq!(state.scroll, 8);
+        let state = scroll(scroll(state, |s| -page(s)), |s| -page(s));
+        assert_eq!(state.scroll, 0);
+
+        // less than a page of history doesn't scroll
+        assert_eq!(scroll(output(5, (80, 10)), page).scroll, 0);
+
+        let mut state = output(20, (80, 10));
+        state.options.display = Display::Inline;
+        assert_eq!(scroll(state, page).scroll, 0);
+    }
+
+    #[test]
+    pub fn builtin_keys_drop_count() {
+        let key = |c| Event::Key(Key::Char(c));
+        let state = normal_mode(key('3'), output(0, (80, 10)));
+        assert_eq!(state.count, Some(3));
+        assert_eq!(normal_mode(key('k'), state).count, None);
+
+        let state = normal_mode(key('2'), output(0, (80, 10)));
+        let state = normal_mode(Event::Key(Key::Ctrl('u')), state);
+        assert_eq!(state.count, None);
+    }
+}
+
 /*
 #[cfg(test)]
 mod tests {
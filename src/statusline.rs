@@ -0,0 +1,127 @@
+use super::{
+    config::Display,
+    state::{Mode, State},
+};
+use std::os::unix::process::ExitStatusExt;
+
+/// The default `statusline`.
+pub const STATUSLINE: &str = " %m %k%=%r %s %p ";
+
+/// Fill in a `statusline` format for `state`, `width` columns wide:
+///
+/// - `%m` the mode
+/// - `%k` the count and keys typed so far for a mapping
+/// - `%p` the program's pid, or how it exited
+/// - `%s` how far the output is scrolled up
+/// - `%r` the progress of a replay
+/// - `%=` what follows goes on the right
+/// - `%%` a `%`
+pub fn render(format: &str, state: &State, width: usize) -> String {
+    let mut sides = (String::new(), String::new());
+    let mut right = false;
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        let side = if right { &mut sides.1 } else { &mut sides.0 };
+        if c != '%' {
+            side.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('m') => side.push_str(mode(&state.mode)),
+            // the command line also lives in `State::command`
+            Some('k') if matches!(state.mode, Mode::Normal | Mode::Insert) => {
+                if let Some(count) = state.count {
+                    side.push_str(&count.to_string());
+                }
+                side.push_str(&state.command.to_string());
+            }
+            Some('k') => {}
+            Some('p') => side.push_str(&process(state)),
+            Some('s') => side.push_str(&scroll(state)),
+            Some('r') => {
+                if let Some(replay) = &state.replay {
+                    side.push_str(&replay.progress());
+                }
+            }
+            Some('=') => right = true,
+            Some(c) => side.push(c),
+            None => side.push('%'),
+        }
+    }
+
+    let (left, right) = sides;
+    let used = left.chars().count() + right.chars().count();
+    let line = format!(
+        "{}{}{}",
+        left,
+        " ".repeat(width.saturating_sub(used)),
+        right
+    );
+    line.chars().take(width).collect()
+}
+
+fn mode(mode: &Mode) -> &'static str {
+    match mode {
+        Mode::Normal => "NORMAL",
+        Mode::Insert => "INSERT",
+        Mode::Replace => "REPLACE",
+        Mode::Command => "COMMAND",
+        Mode::Search { .. } => "SEARCH",
+        Mode::Execute | Mode::Edit | Mode::Quit => "",
+    }
+}
+
+fn process(state: &State) -> String {
+    match (state.child.pid, state.child.exit) {
+        (_, Some(exit)) => match (exit.code(), exit.signal()) {
+            (Some(code), _) => format!("exited {}", code),
+            (None, Some(signal)) => format!("killed by signal {}", signal),
+            (None, None) => "exited".to_string(),
+        },
+        (Some(pid), None) => format!("pid {}", pid),
+        (None, None) => String::new(),
+    }
+}
+
+fn scroll(state: &State) -> String {
+    match (state.options.display, state.scroll) {
+        (Display::Inline, _) => String::new(),
+        (_, 0) => "bot".to_string(),
+        (_, rows) => format!("+{}", rows),
+    }
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cursor::Cursor, keys::KeyMap};
+
+    fn state() -> State {
+        let mut state =
+            State::new("sqlite3".to_string(), (30, 24), KeyMap::new());
+        state.child.pid = Some(42);
+        state
+    }
+
+    #[test]
+    pub fn statusline_sides() {
+        let mut state = state();
+        assert_eq!(
+            render(STATUSLINE, &state, 30),
+            " NORMAL                pid 42 "
+        );
+        state.count = Some(3);
+        state.command = Cursor::from("g".to_string(), String::new());
+        state.options.display = Display::Fullscreen;
+        state.scroll = 5;
+        assert_eq!(
+            render("%m %k%=%s|100%%", &state, 20),
+            "NORMAL 3g    +5|100%"
+        );
+        assert_eq!(render("%m%=%p", &state, 8), "NORMALpi");
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{anyhow, bail, Result};
+use regex::{Regex, RegexBuilder};
+
+/// A compiled `s/pattern/replacement/flags`.
+#[derive(Clone, Debug)]
+pub struct Substitution {
+    pattern: Regex,
+    replacement: String,
+    global: bool,
+}
+
+impl Substitution {
+    /// Parse everything after the `s`. The first character is the
+    /// delimiter; it can appear in the pattern or replacement escaped with a
+    /// backslash. Flags are `g` (every match) and `i` (ignore case).
+    pub fn parse(spec: &str) -> Result<Self> {
+        let delim = spec
+            .chars()
+            .next()
+            .filter(|c| !c.is_alphanumeric() && !c.is_whitespace())
+            .ok_or_else(|| anyhow!("usage: s/pattern/replacement/flags"))?;
+        let parts = split(&spec[delim.len_utf8()..], delim);
+        let (pattern, replacement, flags) = match &parts[..] {
+            [p] => (p.as_str(), "", ""),
+            [p, r] => (p.as_str(), r.as_str(), ""),
+            [p, r, f] => (p.as_str(), r.as_str(), f.as_str()),
+            _ => bail!("trailing characters: {}", spec),
+        };
+        if pattern.is_empty() {
+            bail!("empty pattern");
+        }
+        if let Some(f) = flags.chars().find(|f| !"gi".contains(*f)) {
+            bail!("unknown flag: {}", f);
+        }
+
+        Ok(Substitution {
+            pattern: RegexBuilder::new(pattern)
+                .case_insensitive(flags.contains('i'))
+                .build()?,
+            replacement: replacement_syntax(replacement),
+            global: flags.contains('g'),
+        })
+    }
+
+    /// The substituted text, or `None` if the pattern does not match.
+    pub fn apply(&self, text: &str) -> Option<String> {
+        if !self.pattern.is_match(text) {
+            return None;
+        }
+        let limit = if self.global { 0 } else { 1 };
+        Some(
+            self.pattern
+                .replacen(text, limit, self.replacement.as_str())
+                .into_owned(),
+        )
+    }
+}
+
+/// Bash's `^old^new^` quick substitution: replace the first literal `old`
+/// in `prev`. Returns `None` when `line` is not a quick substitution.
+pub fn quick(line: &str, prev: &str) -> Option<Result<String>> {
+    let rest = line.strip_prefix('^')?;
+    let mut parts = rest.splitn(3, '^');
+    let old = parts.next().filter(|old| !old.is_empty())?;
+    let new = parts.next()?;
+    if !prev.contains(old) {
+        return Some(Err(anyhow!("substitution failed: {}", old)));
+    }
+    Some(Ok(prev.replacen(old, new, 1)))
+}
+
+/// Split on unescaped `delim`, dropping the escaping backslash from `\delim`.
+pub fn split(text: &str, delim: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&delim) => {
+                parts.last_mut().unwrap().push(delim);
+                chars.next();
+            }
+            '\\' => {
+                parts.last_mut().unwrap().push(c);
+                parts.last_mut().unwrap().extend(chars.next());
+            }
+            c if c == delim => parts.push(String::new()),
+            c => parts.last_mut().unwrap().push(c),
+        }
+    }
+    if parts.len() > 1 && parts.last().is_some_and(|p| p.is_empty()) {
+        parts.pop();
+    }
+    parts
+}
+
+/// Translate vim's `&` and `\1` replacement syntax into the regex crate's.
+fn replacement_syntax(replacement: &str) -> String {
+    let mut out = String::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => out.push_str("${0}"),
+            '$' => out.push_str("$$"),
+            '\\' => match chars.next() {
+                Some(d) if d.is_ascii_digit() => {
+                    out.push_str(&format!("${{{}}}", d))
+                }
+                Some('$') => out.push_str("$$"),
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(e) => out.push(e),
+                None => out.push('\\'),
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn substitute_first() {
+        let sub = Substitution::parse("/users/accounts/").unwrap();
+        assert_eq!(
+            sub.apply("select * from users join users").unwrap(),
+            "select * from accounts join users"
+        );
+    }
+
+    #[test]
+    pub fn substitute_global_with_groups() {
+        let sub = Substitution::parse(r"#(\w+)_id#\1.id&#g").unwrap();
+        assert_eq!(
+            sub.apply("user_id, role_id").unwrap(),
+            "user.iduser_id, role.idrole_id"
+        );
+    }
+
+    #[test]
+    pub fn substitute_escaped_delimiter() {
+        let sub = Substitution::parse(r"/a\/b/c").unwrap();
+        assert_eq!(sub.apply("a/b").unwrap(), "c");
+        assert_eq!(sub.apply("ab"), None);
+    }
+
+    #[test]
+    pub fn substitute_bad_flag() {
+        assert!(Substitution::parse("/a/b/x").is_err());
+    }
+
+    #[test]
+    pub fn quick_substitution() {
+        let prev = "select * from users where users.id = 1";
+        assert_eq!(
+            quick("^users^accounts", prev).unwrap().unwrap(),
+            "select * from accounts where users.id = 1"
+        );
+        assert!(quick("^nope^x^", prev).unwrap().is_err());
+        assert!(quick("select 1", prev).is_none());
+    }
+}
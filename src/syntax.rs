@@ -0,0 +1,320 @@
+use super::{config::Options, cursor::PAIRS};
+
+/// What a lexer makes of a character of the input line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Token {
+    Plain,
+    Keyword,
+    String,
+    Number,
+    Comment,
+}
+
+/// How a language writes comments, strings and keywords.
+#[derive(Debug)]
+pub struct Lexer {
+    pub name: &'static str,
+    /// for the file `v` edits the input in, so the editor knows the language
+    pub extension: &'static str,
+    /// start a comment running to the end of the line
+    line_comments: &'static [&'static str],
+    /// only where a word could start, as with `#` in the shell
+    comment_starts_word: bool,
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// characters besides letters, digits and `_` that make up a word
+    word: &'static str,
+    /// separated by whitespace
+    keywords: &'static str,
+    ignore_case: bool,
+}
+
+/// The built-in lexers `set syntax` chooses from.
+static LEXERS: &[Lexer] = &[
+    Lexer {
+        name: "sql",
+        extension: "sql",
+        line_comments: &["--"],
+        comment_starts_word: false,
+        block_comment: Some(("/*", "*/")),
+        quotes: &['\'', '"', '`'],
+        word: "",
+        keywords: "\
+            all alter and as asc begin between by case commit create \
+            default delete desc distinct drop else end exists foreign \
+            from group having in index inner insert into is join key \
+            left like limit not null offset on or order outer primary \
+            references returning right rollback select set table then \
+            union update values view when where with",
+        ignore_case: true,
+    },
+    Lexer {
+        name: "python",
+        extension: "py",
+        line_comments: &["#"],
+        comment_starts_word: false,
+        block_comment: None,
+        quotes: &['\'', '"'],
+        word: "",
+        keywords: "\
+            False None True and as assert async await break class \
+            continue def del elif else except finally for from global if \
+            import in is lambda nonlocal not or pass raise return try \
+            while with yield",
+        ignore_case: false,
+    },
+    Lexer {
+        name: "shell",
+        extension: "sh",
+        line_comments: &["#"],
+        comment_starts_word: true,
+        block_comment: None,
+        quotes: &['\'', '"', '`'],
+        word: "",
+        keywords: "\
+            case do done elif else esac export fi for function if in \
+            local readonly return select then time until while",
+        ignore_case: false,
+    },
+    Lexer {
+        name: "lisp",
+        extension: "lisp",
+        line_comments: &[";"],
+        comment_starts_word: false,
+        block_comment: Some(("#|", "|#")),
+        quotes: &['"'],
+        word: "-*+/<>=!?:&%$",
+        keywords: "\
+            and begin case cond define defmacro defn defparameter defun \
+            defvar do if lambda let let* letrec loop or progn quote set! \
+            setf setq unless when",
+        ignore_case: false,
+    },
+];
+
+/// For plain text: only the `keywords` option is highlighted.
+pub static NONE: Lexer = Lexer {
+    name: "",
+    extension: "txt",
+    line_comments: &[],
+    comment_starts_word: false,
+    block_comment: None,
+    quotes: &[],
+    word: "",
+    keywords: "",
+    ignore_case: false,
+};
+
+/// How `incomplete` reads input with no `syntax`: brackets in single or
+/// double quotes are not counted.
+static QUOTED: Lexer = Lexer {
+    name: "",
+    extension: "txt",
+    line_comments: &[],
+    comment_starts_word: false,
+    block_comment: None,
+    quotes: &['\'', '"'],
+    word: "",
+    keywords: "",
+    ignore_case: false,
+};
+
+/// The lexer called `name`, or the one for plain text when it is empty.
+pub fn lexer(name: &str) -> Option<&'static Lexer> {
+    match name {
+        "" => Some(&NONE),
+        name => LEXERS.iter().find(|l| l.name == name),
+    }
+}
+
+impl Lexer {
+    fn is_word(&self, c: char) -> bool {
+        c.is_alphanumeric() || c == '_' || self.word.contains(c)
+    }
+
+    fn is_keyword(&self, word: &str, extra: &[String]) -> bool {
+        let same = |k: &str| match self.ignore_case {
+            true => k.eq_ignore_ascii_case(word),
+            false => k == word,
+        };
+        self.keywords.split_whitespace().any(same)
+            || extra.iter().any(|k| same(k))
+    }
+
+    /// A token for each character of `text`, with `extra` keywords on top
+    /// of the language's own.
+    pub fn tokens(&self, text: &str, extra: &[String]) -> Vec<Token> {
+        self.scan(text, extra).0
+    }
+
+    /// The tokens of `text`, and whether it ends inside a string or a
+    /// block comment.
+    fn scan(&self, text: &str, extra: &[String]) -> (Vec<Token>, bool) {
+        let chars: Vec<char> = text.chars().collect();
+        let at = |i: usize, s: &str| {
+            s.chars()
+                .enumerate()
+                .all(|(j, c)| chars.get(i + j) == Some(&c))
+        };
+        let mut tokens = vec![Token::Plain; chars.len()];
+        let mut i = 0;
+        let mut unclosed = false;
+        while i < chars.len() {
+            let starts_word = i == 0 || !self.is_word(chars[i - 1]);
+            let (token, end) = if self.line_comments.iter().any(|s| at(i, s))
+                && (!self.comment_starts_word
+                    || i == 0
+                    || chars[i - 1].is_whitespace())
+            {
+                let end = (i..chars.len()).find(|&j| chars[j] == '\n');
+                (Token::Comment, end.unwrap_or(chars.len()))
+            } else if let Some((open, close)) =
+                self.block_comment.filter(|(open, _)| at(i, open))
+            {
+                let from = i + open.chars().count();
+                let end = (from..chars.len()).find(|&j| at(j, close));
+                unclosed = end.is_none();
+                let end =
+                    end.map_or(chars.len(), |j| j + close.chars().count());
+                (Token::Comment, end)
+            } else if self.quotes.contains(&chars[i]) {
+                let mut j = i + 1;
+                while j < chars.len() && chars[j] != chars[i] {
+                    j += if chars[j] == '\\' { 2 } else { 1 };
+                }
+                unclosed = j >= chars.len();
+                (Token::String, (j + 1).min(chars.len()))
+            } else if starts_word && self.is_word(chars[i]) {
+                // a number goes on through a decimal point
+                let number = chars[i].is_ascii_digit();
+                let part = |c: char| self.is_word(c) || (number && c == '.');
+                let end = (i..chars.len())
+                    .find(|&j| !part(chars[j]))
+                    .unwrap_or(chars.len());
+                let word: String = chars[i..end].iter().collect();
+                let token = if number {
+                    Token::Number
+                } else if self.is_keyword(&word, extra) {
+                    Token::Keyword
+                } else {
+                    Token::Plain
+                };
+                (token, end)
+            } else {
+                (Token::Plain, i + 1)
+            };
+            tokens[i..end].iter_mut().for_each(|t| *t = token);
+            i = end;
+        }
+        (tokens, unclosed)
+    }
+}
+
+/// Whether the input `text` needs more lines before it is sent: it ends
+/// with a backslash, `continuation` matches it or, as the `syntax` lexer
+/// sees it, it leaves brackets, a string or a comment open. Without a
+/// `syntax`, single and double quotes are taken to make strings.
+pub fn incomplete(text: &str, options: &Options) -> bool {
+    let continued = options.continuation.as_ref();
+    if text.ends_with('\\') || continued.is_some_and(|c| c.is_match(text)) {
+        return true;
+    }
+    let lexer = match lexer(&options.syntax) {
+        Some(lexer) if !lexer.name.is_empty() => lexer,
+        _ => &QUOTED,
+    };
+    let (tokens, unclosed) = lexer.scan(text, &[]);
+    let mut depth = 0;
+    for (c, token) in text.chars().zip(tokens) {
+        match token {
+            Token::Plain if PAIRS.iter().any(|(open, _)| *open == c) => {
+                depth += 1
+            }
+            Token::Plain if PAIRS.iter().any(|(_, close)| *close == c) => {
+                depth -= 1
+            }
+            _ => {}
+        }
+    }
+    unclosed || depth > 0
+}
+
+/*
+ * Tests
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A letter for the token of each character of `text`.
+    fn kinds(syntax: &str, text: &str, extra: &[String]) -> String {
+        lexer(syntax)
+            .unwrap()
+            .tokens(text, extra)
+            .iter()
+            .map(|t| match t {
+                Token::Plain => '.',
+                Token::Keyword => 'k',
+                Token::String => 's',
+                Token::Number => 'n',
+                Token::Comment => 'c',
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn sql_tokens() {
+        assert_eq!(
+            kinds("sql", "Select 'it''s', 1.5 from t1 -- x", &[]),
+            "kkkkkk.sssssss..nnn.kkkk....cccc"
+        );
+        assert_eq!(kinds("sql", "a /* b */ c", &[]), "..ccccccc..");
+    }
+
+    #[test]
+    pub fn shell_and_lisp_tokens() {
+        assert_eq!(kinds("shell", "if a#b; then # c", &[]), "kk......kkkk.ccc");
+        assert_eq!(
+            kinds("lisp", r#"(let* ((x "a\"")) ; y"#, &[]),
+            ".kkkk.....sssss...ccc"
+        );
+    }
+
+    #[test]
+    pub fn extra_keywords() {
+        let extra = vec!["load".to_string()];
+        assert_eq!(kinds("", "load 'x' # 2", &extra), "kkkk.......n");
+        assert!(lexer("cobol").is_none());
+    }
+
+    #[test]
+    pub fn incomplete_input() {
+        let mut options = Options::new();
+        assert!(incomplete("ls \\", &options));
+        // with no syntax, brackets in quotes don't count
+        assert!(incomplete("f(1,", &options));
+        assert!(incomplete("echo \"abc", &options));
+        assert!(!incomplete("echo \"(\"", &options));
+        assert!(!incomplete("select ')(' ;", &options));
+        assert!(!incomplete("f(1,\n2)", &options));
+        options.syntax = "sql".to_string();
+        assert!(incomplete("select f(1,", &options));
+        assert!(!incomplete("select f(1,\n2)", &options));
+        assert!(!incomplete("select ')(' ;", &options));
+        options.syntax = "python".to_string();
+        assert!(!incomplete("print(\":(\")", &options));
+        assert!(!incomplete("print(')')", &options));
+        assert!(incomplete("s = 'abc", &options));
+        assert!(!incomplete("x = 1  # (", &options));
+        assert!(!incomplete("def f():", &options));
+        // a block opened with a colon goes on until an empty line
+        let block = r"(?s)^[^\n]*:\s*(\n.*\S)?$";
+        options.continuation = Some(regex::Regex::new(block).unwrap());
+        assert!(incomplete("def f():", &options));
+        assert!(incomplete("def f():\n    return 1", &options));
+        assert!(incomplete("def f():\n    return (1,\n2)", &options));
+        assert!(!incomplete("def f():\n    return 1\n", &options));
+        assert!(!incomplete("x = {1: 2}", &options));
+    }
+}